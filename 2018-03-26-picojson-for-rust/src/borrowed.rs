@@ -0,0 +1,552 @@
+//! Values which borrow strings from the input, to parse without copying.
+//!
+//! Strings and keys without escape sequences point into the input.
+//! Others are decoded into owned strings.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use {
+    _parse_key, _parse_string, parse_chars_with, parse_input, DuplicateKeys, Error, ErrorKind,
+    Input, Number, ParseContext, ParseOptions, StringSink, Value, ValueKey, ValueLike,
+};
+
+#[cfg(not(feature = "preserve_order"))]
+type MapImpl<'a> = ::std::collections::BTreeMap<Cow<'a, str>, BorrowedValue<'a>>;
+
+#[cfg(feature = "preserve_order")]
+type MapImpl<'a> = ::indexmap::IndexMap<Cow<'a, str>, BorrowedValue<'a>>;
+
+pub type BorrowedArray<'a> = Vec<BorrowedValue<'a>>;
+
+/// Represents a JSON object of borrowed values.
+/// Members are ordered in the same way as `Map`.
+#[derive(Clone, Default, PartialEq)]
+pub struct BorrowedMap<'a> {
+    map: MapImpl<'a>,
+}
+
+impl<'a> BorrowedMap<'a> {
+    pub fn new() -> BorrowedMap<'a> {
+        BorrowedMap {
+            map: MapImpl::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&BorrowedValue<'a>> {
+        self.map.get(key)
+    }
+
+    /// Inserts a member and returns the previous value.
+    pub fn insert(
+        &mut self,
+        key: Cow<'a, str>,
+        value: BorrowedValue<'a>,
+    ) -> Option<BorrowedValue<'a>> {
+        self.map.insert(key, value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &BorrowedValue<'a>)> {
+        self.map.iter().map(|(key, value)| (key.as_ref(), value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.map.keys().map(|key| key.as_ref())
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &BorrowedValue<'a>> {
+        self.map.values()
+    }
+}
+
+impl<'a> fmt::Debug for BorrowedMap<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Represents a JSON value whose strings may borrow from the input.
+#[derive(Clone, PartialEq, Debug)]
+pub enum BorrowedValue<'a> {
+    Null,
+    Boolean(bool),
+    Number(Number),
+    String(Cow<'a, str>),
+    Array(BorrowedArray<'a>),
+    Object(BorrowedMap<'a>),
+}
+
+/// Represents a type which a `BorrowedValue` may contain.
+/// Strings are accessed as `str`.
+pub trait BorrowedValueKind<'a> {
+    fn try_borrow<'v>(value: &'v BorrowedValue<'a>) -> Option<&'v Self>;
+}
+
+impl<'a> BorrowedValueKind<'a> for () {
+    fn try_borrow<'v>(value: &'v BorrowedValue<'a>) -> Option<&'v Self> {
+        match value {
+            BorrowedValue::Null => Some(&()),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> BorrowedValueKind<'a> for bool {
+    fn try_borrow<'v>(value: &'v BorrowedValue<'a>) -> Option<&'v Self> {
+        match value {
+            BorrowedValue::Boolean(it) => Some(it),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> BorrowedValueKind<'a> for f64 {
+    fn try_borrow<'v>(value: &'v BorrowedValue<'a>) -> Option<&'v Self> {
+        match value {
+            BorrowedValue::Number(it) => Some(&it.value),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> BorrowedValueKind<'a> for Number {
+    fn try_borrow<'v>(value: &'v BorrowedValue<'a>) -> Option<&'v Self> {
+        match value {
+            BorrowedValue::Number(it) => Some(it),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> BorrowedValueKind<'a> for str {
+    fn try_borrow<'v>(value: &'v BorrowedValue<'a>) -> Option<&'v Self> {
+        match value {
+            BorrowedValue::String(it) => Some(it),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> BorrowedValueKind<'a> for BorrowedArray<'a> {
+    fn try_borrow<'v>(value: &'v BorrowedValue<'a>) -> Option<&'v Self> {
+        match value {
+            BorrowedValue::Array(it) => Some(it),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> BorrowedValueKind<'a> for BorrowedMap<'a> {
+    fn try_borrow<'v>(value: &'v BorrowedValue<'a>) -> Option<&'v Self> {
+        match value {
+            BorrowedValue::Object(it) => Some(it),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> BorrowedValue<'a> {
+    pub fn try_as<T: BorrowedValueKind<'a> + ?Sized>(&self) -> Option<&T> {
+        T::try_borrow(self)
+    }
+
+    pub fn is_of<T: BorrowedValueKind<'a> + ?Sized>(&self) -> bool {
+        self.try_as::<T>().is_some()
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.is_of::<()>()
+    }
+
+    pub fn as_bool(&self) -> Option<&bool> {
+        self.try_as::<bool>()
+    }
+
+    pub fn as_number(&self) -> Option<&f64> {
+        self.try_as::<f64>()
+    }
+
+    /// Gets the value as `i64` if it's an integer in the range.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.try_as::<Number>().and_then(|n| n.as_i64())
+    }
+
+    /// Gets the value as `u64` if it's a non-negative integer in the range.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.try_as::<Number>().and_then(|n| n.as_u64())
+    }
+
+    pub fn as_string(&self) -> Option<&str> {
+        self.try_as::<str>()
+    }
+
+    pub fn as_array(&self) -> Option<&BorrowedArray<'a>> {
+        self.try_as::<BorrowedArray<'a>>()
+    }
+
+    pub fn as_object(&self) -> Option<&BorrowedMap<'a>> {
+        self.try_as::<BorrowedMap<'a>>()
+    }
+
+    /// Determines if the value is empty, i.e., an empty array or object.
+    pub fn is_empty(&self) -> bool {
+        match *self {
+            BorrowedValue::Array(ref array) => array.is_empty(),
+            BorrowedValue::Object(ref object) => object.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Determines if the value is a collection and has item for the specified key.
+    pub fn has<'k, K: Into<ValueKey<'k>>>(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Gets an item for the specified key in the value if it's a collection.
+    pub fn get<'k, K: Into<ValueKey<'k>>>(&self, key: K) -> Option<&BorrowedValue<'a>> {
+        match (self, key.into()) {
+            (BorrowedValue::Array(array), ValueKey::Index(index)) => array.get(index),
+            (BorrowedValue::Object(object), ValueKey::Key(key)) => object.get(key),
+            _ => None,
+        }
+    }
+
+    /// Copies the value into an owned `Value`.
+    pub fn to_value(&self) -> Value {
+        match *self {
+            BorrowedValue::Null => Value::Null,
+            BorrowedValue::Boolean(value) => Value::Boolean(value),
+            BorrowedValue::Number(ref value) => Value::Number(value.clone()),
+            BorrowedValue::String(ref value) => Value::String(value.to_string()),
+            BorrowedValue::Array(ref array) => {
+                Value::Array(array.iter().map(BorrowedValue::to_value).collect())
+            }
+            BorrowedValue::Object(ref object) => Value::Object(
+                object
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_value()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Moves the value into an owned `Value`, reusing decoded strings.
+impl<'a> ValueLike for BorrowedValue<'a> {
+    fn into_value(self) -> Value {
+        match self {
+            BorrowedValue::Null => Value::Null,
+            BorrowedValue::Boolean(value) => Value::Boolean(value),
+            BorrowedValue::Number(value) => Value::Number(value),
+            BorrowedValue::String(value) => Value::String(value.into_owned()),
+            BorrowedValue::Array(array) => {
+                Value::Array(array.into_iter().map(ValueLike::into_value).collect())
+            }
+            BorrowedValue::Object(object) => Value::Object(
+                object
+                    .map
+                    .into_iter()
+                    .map(|(key, value)| (key.into_owned(), value.into_value()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl<'a> From<BorrowedValue<'a>> for Value {
+    fn from(value: BorrowedValue<'a>) -> Value {
+        value.into_value()
+    }
+}
+
+/// A string sink which keeps borrowing the input while decoded characters match it.
+struct SourceString<'a> {
+    source: &'a str,
+    start: usize,
+    len: usize,
+    owned: Option<String>,
+}
+
+impl<'a> SourceString<'a> {
+    fn into_cow(self) -> Cow<'a, str> {
+        match self.owned {
+            Some(owned) => Cow::Owned(owned),
+            None => Cow::Borrowed(&self.source[self.start..self.start + self.len]),
+        }
+    }
+}
+
+impl<'a> StringSink for SourceString<'a> {
+    fn push(&mut self, c: char) {
+        if let Some(ref mut owned) = self.owned {
+            owned.push(c);
+            return;
+        }
+
+        let end = self.start + self.len;
+        if self.source[end..].starts_with(c) {
+            self.len += c.len_utf8();
+        } else {
+            let mut owned = self.source[self.start..end].to_string();
+            owned.push(c);
+            self.owned = Some(owned);
+        }
+    }
+}
+
+struct BorrowedParseContext<'a, 'o> {
+    source: &'a str,
+    out: &'o mut BorrowedValue<'a>,
+}
+
+impl<'a, 'o> BorrowedParseContext<'a, 'o> {
+    fn parse_child<I: Iterator<Item = char>>(
+        &self,
+        input: &mut Input<I>,
+    ) -> Option<BorrowedValue<'a>> {
+        let mut value = BorrowedValue::Null;
+        let ok = {
+            let mut subcontext = BorrowedParseContext {
+                source: self.source,
+                out: &mut value,
+            };
+            parse_input(&mut subcontext, input)
+        };
+        if ok {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Parses the value of a member and inserts it to the object.
+    fn parse_member<I: Iterator<Item = char>>(
+        &mut self,
+        input: &mut Input<I>,
+        key: Cow<'a, str>,
+    ) -> bool {
+        let duplicate = self.out.as_object().unwrap().contains_key(&key);
+        if duplicate && input.options.duplicate_keys == DuplicateKeys::Error {
+            let position = input.key_position;
+            return input.fail_at(ErrorKind::DuplicateKey(key.into_owned()), position);
+        }
+
+        let value = match self.parse_child(input) {
+            Some(value) => value,
+            None => return false,
+        };
+
+        if duplicate && input.options.duplicate_keys == DuplicateKeys::FirstWins {
+            return true;
+        }
+
+        if let BorrowedValue::Object(ref mut object) = *self.out {
+            object.insert(key, value);
+        }
+        true
+    }
+}
+
+impl<'a, 'o> ParseContext for BorrowedParseContext<'a, 'o> {
+    fn set_null(&mut self) -> bool {
+        *self.out = BorrowedValue::Null;
+        true
+    }
+
+    fn set_bool(&mut self, value: bool) -> bool {
+        *self.out = BorrowedValue::Boolean(value);
+        true
+    }
+
+    fn set_number(&mut self, value: Number) -> bool {
+        *self.out = BorrowedValue::Number(value);
+        true
+    }
+
+    fn set_string(&mut self, value: String) -> bool {
+        *self.out = BorrowedValue::String(Cow::Owned(value));
+        true
+    }
+
+    fn parse_string<I: Iterator<Item = char>>(&mut self, input: &mut Input<I>) -> bool {
+        // The input is at the opening quote, which is 1 byte.
+        let mut out = SourceString {
+            source: self.source,
+            start: input.position.offset + 1,
+            len: 0,
+            owned: None,
+        };
+        if !_parse_string(&mut out, input) {
+            return false;
+        }
+        *self.out = BorrowedValue::String(out.into_cow());
+        true
+    }
+
+    fn parse_array_start(&mut self) -> bool {
+        *self.out = BorrowedValue::Array(BorrowedArray::new());
+        true
+    }
+
+    fn parse_array_item<I: Iterator<Item = char>>(
+        &mut self,
+        input: &mut Input<I>,
+        _size: usize,
+    ) -> bool {
+        let value = match self.parse_child(input) {
+            Some(value) => value,
+            None => return false,
+        };
+
+        if let BorrowedValue::Array(ref mut array) = *self.out {
+            array.push(value);
+        }
+        true
+    }
+
+    fn parse_array_stop(&mut self, _size: usize) -> bool {
+        true
+    }
+
+    fn parse_object_start(&mut self) -> bool {
+        *self.out = BorrowedValue::Object(BorrowedMap::new());
+        true
+    }
+
+    fn parse_object_member<I: Iterator<Item = char>>(&mut self, input: &mut Input<I>) -> bool {
+        // The input is at the key. The opening quote, if any, is 1 byte.
+        let position = input.key_position.offset;
+        let start = match self.source[position..].chars().next() {
+            Some('"') | Some('\'') => position + 1,
+            _ => position,
+        };
+        let mut key = SourceString {
+            source: self.source,
+            start,
+            len: 0,
+            owned: None,
+        };
+        _parse_key(&mut key, input) && self.parse_member(input, key.into_cow())
+    }
+
+    fn parse_object_item<I: Iterator<Item = char>>(
+        &mut self,
+        input: &mut Input<I>,
+        key: String,
+    ) -> bool {
+        self.parse_member(input, Cow::Owned(key))
+    }
+}
+
+/// Parses a string into a value which borrows strings from it.
+pub fn parse_borrowed(s: &str) -> Result<BorrowedValue<'_>, Error> {
+    parse_borrowed_with_options(s, &ParseOptions::default())
+}
+
+pub fn parse_borrowed_with_options<'a>(
+    s: &'a str,
+    options: &ParseOptions,
+) -> Result<BorrowedValue<'a>, Error> {
+    let mut out = BorrowedValue::Null;
+    {
+        let mut context = BorrowedParseContext {
+            source: s,
+            out: &mut out,
+        };
+        let mut input = Input::with_options(s.chars(), options.clone());
+        parse_chars_with(&mut context, &mut input)?;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_string;
+
+    fn is_borrowed(value: &BorrowedValue) -> bool {
+        matches!(*value, BorrowedValue::String(Cow::Borrowed(_)))
+    }
+
+    #[test]
+    fn test_parse_borrowed() {
+        let source = r#"{"plain": "text", "escaped": "a\nb", "ü": ["ö", "\u00e9", 1, true, null]}"#;
+        let value = parse_borrowed(source).unwrap();
+
+        let plain = value.get("plain").unwrap();
+        assert_eq!(plain.as_string(), Some("text"));
+        assert!(is_borrowed(plain));
+
+        let escaped = value.get("escaped").unwrap();
+        assert_eq!(escaped.as_string(), Some("a\nb"));
+        assert!(!is_borrowed(escaped));
+
+        let items = value.get("ü").unwrap();
+        assert!(items.is_of::<BorrowedArray>());
+        assert!(is_borrowed(items.get(0).unwrap()));
+        assert_eq!(items.get(1).and_then(|v| v.as_string()), Some("é"));
+        assert!(!is_borrowed(items.get(1).unwrap()));
+        assert_eq!(items.get(2).and_then(|v| v.as_i64()), Some(1));
+        assert_eq!(items.get(3).and_then(|v| v.as_bool()), Some(&true));
+        assert!(items.get(4).unwrap().is_null());
+        assert!(items.get(5).is_none());
+
+        match value {
+            BorrowedValue::Object(ref object) => {
+                assert!(object
+                    .map
+                    .keys()
+                    .all(|key| matches!(*key, Cow::Borrowed(_))));
+            }
+            _ => panic!("Expected an object."),
+        }
+
+        assert_eq!(value.to_value(), parse_string(source).unwrap());
+        assert_eq!(value.clone().into_value(), parse_string(source).unwrap());
+        let owned: Value = value.into();
+        assert_eq!(owned, parse_string(source).unwrap());
+    }
+
+    #[test]
+    fn test_parse_borrowed_keys() {
+        let source = r#"{ key: 'single', "esc\"aped": 1, 'quoted': 2 }"#;
+        let value = parse_borrowed_with_options(source, &ParseOptions::json5()).unwrap();
+        let object = value.as_object().unwrap();
+        let owned = object
+            .map
+            .iter()
+            .filter(|&(key, _)| matches!(*key, Cow::Owned(_)))
+            .map(|(key, _)| key.as_ref())
+            .collect::<Vec<_>>();
+        assert_eq!(owned, vec!["esc\"aped"]);
+        assert_eq!(value.get("key").and_then(|v| v.as_string()), Some("single"));
+        assert!(is_borrowed(value.get("key").unwrap()));
+    }
+
+    #[test]
+    fn test_parse_borrowed_errors() {
+        let options = ParseOptions {
+            duplicate_keys: DuplicateKeys::Error,
+            ..ParseOptions::default()
+        };
+        let err = parse_borrowed_with_options(r#"{"a": 1, "a": 2}"#, &options)
+            .expect_err("Expected an error.");
+        assert_eq!(err.kind(), &ErrorKind::DuplicateKey("a".to_string()));
+        assert_eq!(err.column(), 10);
+
+        let err = parse_borrowed(r#"["a", "b"#).expect_err("Expected an error.");
+        assert_eq!(err.kind(), &ErrorKind::UnterminatedString);
+    }
+}
@@ -0,0 +1,1035 @@
+//! JSONPath ([RFC 9535](https://www.rfc-editor.org/rfc/rfc9535)).
+//!
+//! Supported: child and descendant segments, name, wildcard, index, slice and filter selectors.
+//! Filters support comparisons, `&&`, `||`, `!`, existence tests
+//! and the functions `length`, `count`, `value`, `match` and `search`.
+//! `match` and `search` require `regex` feature.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use regex_impl::Regex;
+use {Number, Value};
+
+/// Represents an error in a JSONPath expression.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct JsonPathError {
+    offset: usize,
+    message: String,
+}
+
+impl JsonPathError {
+    /// Gets the byte offset in the expression where the error is detected.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for JsonPathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl ::std::error::Error for JsonPathError {}
+
+/// Represents a parsed JSONPath query such as `$.store.book[?@.price < 10].title`.
+#[derive(Clone, Debug)]
+pub struct JsonPath {
+    query: Query,
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<JsonPath, JsonPathError> {
+        let mut parser = Parser {
+            source: path,
+            offset: 0,
+        };
+        if !parser.eat('$') {
+            return parser.error("Expected '$'");
+        }
+        let segments = parser.parse_segments()?;
+        parser.skip_ws();
+        if parser.peek().is_some() {
+            return parser.error("Expected '.' or '['");
+        }
+
+        Ok(JsonPath {
+            query: Query {
+                relative: false,
+                segments,
+            },
+        })
+    }
+
+    /// Selects values in document order. The same value may appear more than once.
+    pub fn query<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        self.query.select(value, value)
+    }
+}
+
+impl Value {
+    /// Selects values by a JSONPath expression.
+    pub fn query(&self, path: &str) -> Result<Vec<&Value>, JsonPathError> {
+        Ok(JsonPath::parse(path)?.query(self))
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Query {
+    /// Starts with `@` rather than `$`.
+    relative: bool,
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug)]
+struct Segment {
+    /// `..`, which applies the selectors to the value and all descendants.
+    descendant: bool,
+    selectors: Vec<Selector>,
+}
+
+#[derive(Clone, Debug)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice {
+        start: Option<i64>,
+        end: Option<i64>,
+        step: Option<i64>,
+    },
+    Filter(Filter),
+}
+
+#[derive(Clone, Debug)]
+enum Filter {
+    Or(Vec<Filter>),
+    And(Vec<Filter>),
+    Not(Box<Filter>),
+    Compare(Comparable, CompareOp, Comparable),
+    /// Tests if the query selects something.
+    Exists(Query),
+    Match(Box<RegexMatch>),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// An operand of comparisons, which evaluates to a value or nothing.
+#[derive(Clone, Debug)]
+enum Comparable {
+    Literal(Value),
+    /// A query which selects at most one value.
+    Query(Query),
+    Length(Box<Comparable>),
+    Count(Query),
+    Value(Query),
+}
+
+/// `match` (whole string) or `search` (substring) function.
+#[derive(Clone, Debug)]
+struct RegexMatch {
+    value: Comparable,
+    pattern: Comparable,
+    /// Compiled in advance if the pattern is a literal.
+    regex: Option<Regex>,
+    whole: bool,
+}
+
+/// A parsed operand before it's known how it's used.
+enum Operand {
+    Comparable(Comparable),
+    Query(Query),
+    Match(RegexMatch),
+}
+
+impl Query {
+    /// Determines if the query selects at most one value.
+    fn is_singular(&self) -> bool {
+        self.segments.iter().all(|segment| {
+            !segment.descendant
+                && matches!(
+                    segment.selectors[..],
+                    [Selector::Name(_)] | [Selector::Index(_)]
+                )
+        })
+    }
+
+    fn select<'a>(&self, root: &'a Value, current: &'a Value) -> Vec<&'a Value> {
+        let mut nodes = vec![if self.relative { current } else { root }];
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for node in nodes {
+                if segment.descendant {
+                    for node in descendants(node) {
+                        segment.select(node, root, &mut next);
+                    }
+                } else {
+                    segment.select(node, root, &mut next);
+                }
+            }
+            nodes = next;
+        }
+        nodes
+    }
+}
+
+impl Segment {
+    fn select<'a>(&self, node: &'a Value, root: &'a Value, out: &mut Vec<&'a Value>) {
+        for selector in &self.selectors {
+            selector.select(node, root, out);
+        }
+    }
+}
+
+impl Selector {
+    fn select<'a>(&self, node: &'a Value, root: &'a Value, out: &mut Vec<&'a Value>) {
+        match *self {
+            Selector::Name(ref name) => {
+                if let Value::Object(ref object) = *node {
+                    out.extend(object.get(name.as_str()));
+                }
+            }
+            Selector::Wildcard => out.extend(children(node)),
+            Selector::Index(index) => {
+                if let Value::Array(ref items) = *node {
+                    out.extend(normalize_index(index, items.len()).map(|i| &items[i]));
+                }
+            }
+            Selector::Slice { start, end, step } => {
+                if let Value::Array(ref items) = *node {
+                    out.extend(slice_indices(start, end, step, items.len()).map(|i| &items[i]));
+                }
+            }
+            Selector::Filter(ref filter) => {
+                out.extend(children(node).filter(|child| filter.test(root, child)));
+            }
+        }
+    }
+}
+
+impl Filter {
+    fn test(&self, root: &Value, current: &Value) -> bool {
+        match *self {
+            Filter::Or(ref filters) => filters.iter().any(|f| f.test(root, current)),
+            Filter::And(ref filters) => filters.iter().all(|f| f.test(root, current)),
+            Filter::Not(ref filter) => !filter.test(root, current),
+            Filter::Compare(ref l, op, ref r) => {
+                let l = l.eval(root, current);
+                let r = r.eval(root, current);
+                compare(
+                    l.as_ref().map(|v| v.as_ref()),
+                    op,
+                    r.as_ref().map(|v| v.as_ref()),
+                )
+            }
+            Filter::Exists(ref query) => !query.select(root, current).is_empty(),
+            Filter::Match(ref m) => m.test(root, current),
+        }
+    }
+}
+
+impl Comparable {
+    /// Evaluates to a value, or `None` if nothing is selected.
+    fn eval<'a>(&'a self, root: &'a Value, current: &'a Value) -> Option<Cow<'a, Value>> {
+        match *self {
+            Comparable::Literal(ref value) => Some(Cow::Borrowed(value)),
+            Comparable::Query(ref query) => query.select(root, current).pop().map(Cow::Borrowed),
+            Comparable::Length(ref arg) => {
+                let len = match *arg.eval(root, current)? {
+                    Value::String(ref s) => s.chars().count(),
+                    Value::Array(ref items) => items.len(),
+                    Value::Object(ref object) => object.len(),
+                    _ => return None,
+                };
+                Some(Cow::Owned(Value::Number(Number::from(len as u64))))
+            }
+            Comparable::Count(ref query) => {
+                let count = query.select(root, current).len();
+                Some(Cow::Owned(Value::Number(Number::from(count as u64))))
+            }
+            Comparable::Value(ref query) => {
+                let nodes = query.select(root, current);
+                if nodes.len() == 1 {
+                    Some(Cow::Borrowed(nodes[0]))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl RegexMatch {
+    fn test(&self, root: &Value, current: &Value) -> bool {
+        let value = self.value.eval(root, current);
+        let s = match value.as_ref().map(|v| v.as_ref()) {
+            Some(Value::String(s)) => s,
+            _ => return false,
+        };
+
+        if let Some(ref regex) = self.regex {
+            return regex.is_match(s);
+        }
+        match self
+            .pattern
+            .eval(root, current)
+            .as_ref()
+            .map(|v| v.as_ref())
+        {
+            // An invalid pattern just doesn't match anything.
+            Some(Value::String(pattern)) => {
+                compile_regex(pattern, self.whole).is_some_and(|regex| regex.is_match(s))
+            }
+            _ => false,
+        }
+    }
+}
+
+fn compile_regex(pattern: &str, whole: bool) -> Option<Regex> {
+    if whole {
+        Regex::new(&format!("^(?:{})$", pattern)).ok()
+    } else {
+        Regex::new(pattern).ok()
+    }
+}
+
+fn compare(l: Option<&Value>, op: CompareOp, r: Option<&Value>) -> bool {
+    fn equal(l: Option<&Value>, r: Option<&Value>) -> bool {
+        l == r
+    }
+
+    fn less(l: Option<&Value>, r: Option<&Value>) -> bool {
+        match (l, r) {
+            (Some(Value::Number(l)), Some(Value::Number(r))) => l < r,
+            (Some(Value::String(l)), Some(Value::String(r))) => l < r,
+            _ => false,
+        }
+    }
+
+    match op {
+        CompareOp::Eq => equal(l, r),
+        CompareOp::Ne => !equal(l, r),
+        CompareOp::Lt => less(l, r),
+        CompareOp::Le => less(l, r) || equal(l, r),
+        CompareOp::Gt => less(r, l),
+        CompareOp::Ge => less(r, l) || equal(l, r),
+    }
+}
+
+fn children(node: &Value) -> Box<dyn Iterator<Item = &Value> + '_> {
+    match *node {
+        Value::Array(ref items) => Box::new(items.iter()),
+        Value::Object(ref object) => Box::new(object.values()),
+        _ => Box::new(None.into_iter()),
+    }
+}
+
+/// Lists the value and all descendants in document order.
+fn descendants(node: &Value) -> Vec<&Value> {
+    let mut nodes = Vec::new();
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        nodes.push(node);
+        let len = stack.len();
+        stack.extend(children(node));
+        stack[len..].reverse();
+    }
+    nodes
+}
+
+/// Converts an index, which may count from the end, into an index in the range.
+fn normalize_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { index + len as i64 } else { index };
+    if 0 <= index && index < len as i64 {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+fn slice_indices(
+    start: Option<i64>,
+    end: Option<i64>,
+    step: Option<i64>,
+    len: usize,
+) -> Box<dyn Iterator<Item = usize>> {
+    let len = len as i64;
+    let step = step.unwrap_or(1);
+    let normalize = |i: i64| if i < 0 { len + i } else { i };
+
+    if step > 0 {
+        let lower = normalize(start.unwrap_or(0)).max(0).min(len);
+        let upper = normalize(end.unwrap_or(len)).max(0).min(len);
+        Box::new((lower..upper).step_by(step as usize).map(|i| i as usize))
+    } else if step < 0 {
+        let upper = normalize(start.unwrap_or(len - 1)).max(-1).min(len - 1);
+        let lower = normalize(end.unwrap_or(-len - 1)).max(-1).min(len - 1);
+        Box::new(
+            (lower + 1..upper + 1)
+                .rev()
+                .step_by(step.unsigned_abs() as usize)
+                .map(|i| i as usize),
+        )
+    } else {
+        Box::new(None.into_iter())
+    }
+}
+
+/// Integers in JSONPath are limited to the range `f64` represents exactly.
+const MAX_INT: i64 = (1 << 53) - 1;
+
+struct Parser<'s> {
+    source: &'s str,
+    offset: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.offset..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.eat_str(c.encode_utf8(&mut [0; 4]))
+    }
+
+    fn eat_str(&mut self, s: &str) -> bool {
+        if self.source[self.offset..].starts_with(s) {
+            self.offset += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(' ') | Some('\t') | Some('\r') | Some('\n') = self.peek() {
+            self.offset += 1;
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, JsonPathError> {
+        self.error_at(self.offset, message)
+    }
+
+    fn error_at<T>(&self, offset: usize, message: &str) -> Result<T, JsonPathError> {
+        Err(JsonPathError {
+            offset,
+            message: message.to_string(),
+        })
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), JsonPathError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.error(&format!("Expected {:?}", c))
+        }
+    }
+
+    fn parse_segments(&mut self) -> Result<Vec<Segment>, JsonPathError> {
+        let mut segments = Vec::new();
+        loop {
+            let offset = self.offset;
+            self.skip_ws();
+            match self.peek() {
+                Some('.') | Some('[') => segments.push(self.parse_segment()?),
+                _ => {
+                    self.offset = offset;
+                    return Ok(segments);
+                }
+            }
+        }
+    }
+
+    fn parse_segment(&mut self) -> Result<Segment, JsonPathError> {
+        let descendant = self.eat_str("..");
+        if !descendant && !self.eat('.') {
+            let selectors = self.parse_bracket()?;
+            return Ok(Segment {
+                descendant,
+                selectors,
+            });
+        }
+
+        let selectors = match self.peek() {
+            Some('[') if descendant => self.parse_bracket()?,
+            Some('*') => {
+                self.bump();
+                vec![Selector::Wildcard]
+            }
+            _ => vec![Selector::Name(self.parse_member_name()?)],
+        };
+        Ok(Segment {
+            descendant,
+            selectors,
+        })
+    }
+
+    /// Parses a member name after `.`, such as `.name`.
+    fn parse_member_name(&mut self) -> Result<String, JsonPathError> {
+        let start = self.offset;
+        match self.peek() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' || !c.is_ascii() => {}
+            _ => return self.error("Expected a member name or '*'"),
+        }
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_alphanumeric() || c == '_' || !c.is_ascii()) {
+                break;
+            }
+            self.bump();
+        }
+        Ok(self.source[start..self.offset].to_string())
+    }
+
+    fn parse_bracket(&mut self) -> Result<Vec<Selector>, JsonPathError> {
+        self.expect('[')?;
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_ws();
+            selectors.push(self.parse_selector()?);
+            self.skip_ws();
+            if !self.eat(',') {
+                break;
+            }
+        }
+        self.expect(']')?;
+        Ok(selectors)
+    }
+
+    fn parse_selector(&mut self) -> Result<Selector, JsonPathError> {
+        match self.peek() {
+            Some('\'') | Some('"') => Ok(Selector::Name(self.parse_string()?)),
+            Some('*') => {
+                self.bump();
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.bump();
+                self.skip_ws();
+                Ok(Selector::Filter(self.parse_or()?))
+            }
+            Some(c) if c == '-' || c == ':' || c.is_ascii_digit() => self.parse_index_or_slice(),
+            _ => self.error("Expected a selector"),
+        }
+    }
+
+    fn parse_index_or_slice(&mut self) -> Result<Selector, JsonPathError> {
+        let start = self.parse_opt_int()?;
+        self.skip_ws();
+        if !self.eat(':') {
+            return match start {
+                Some(index) => Ok(Selector::Index(index)),
+                None => self.error("Expected an index"),
+            };
+        }
+
+        self.skip_ws();
+        let end = self.parse_opt_int()?;
+        self.skip_ws();
+        let step = if self.eat(':') {
+            self.skip_ws();
+            self.parse_opt_int()?
+        } else {
+            None
+        };
+        Ok(Selector::Slice { start, end, step })
+    }
+
+    /// Parses an integer if it starts here. Leading zeros aren't allowed.
+    fn parse_opt_int(&mut self) -> Result<Option<i64>, JsonPathError> {
+        let start = self.offset;
+        let negative = self.eat('-');
+        let digits_start = self.offset;
+        while let Some('0'..='9') = self.peek() {
+            self.offset += 1;
+        }
+
+        let digits = &self.source[digits_start..self.offset];
+        if digits.is_empty() {
+            return if negative {
+                self.error("Expected digits")
+            } else {
+                Ok(None)
+            };
+        }
+        if (digits.starts_with('0') && digits.len() > 1) || (negative && digits == "0") {
+            return self.error_at(start, "Invalid integer");
+        }
+        match self.source[start..self.offset].parse::<i64>() {
+            Ok(n) if (-MAX_INT..=MAX_INT).contains(&n) => Ok(Some(n)),
+            _ => self.error_at(start, "Integer out of range"),
+        }
+    }
+
+    /// Parses a string literal quoted with `'` or `"`.
+    fn parse_string(&mut self) -> Result<String, JsonPathError> {
+        let start = self.offset;
+        let quote = self.bump().unwrap();
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None => return self.error_at(start, "Unterminated string"),
+                Some(c) if c == quote => return Ok(out),
+                Some(c) if c < ' ' => return self.error("Control character in string"),
+                Some('\\') => {
+                    let c = match self.bump() {
+                        Some(c) if c == quote || c == '\\' || c == '/' => c,
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.parse_codepoint()?,
+                        _ => return self.error("Invalid escape sequence"),
+                    };
+                    out.push(c);
+                }
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    /// Parses hex digits after `\u`, which may be a surrogate pair.
+    fn parse_codepoint(&mut self) -> Result<char, JsonPathError> {
+        let start = self.offset - 2;
+        let high = self.parse_quadhex()?;
+        let code = match high {
+            0xD800..=0xDBFF => {
+                if !self.eat_str("\\u") {
+                    return self.error_at(start, "Lone surrogate");
+                }
+                match self.parse_quadhex()? {
+                    low @ 0xDC00..=0xDFFF => 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00),
+                    _ => return self.error_at(start, "Lone surrogate"),
+                }
+            }
+            0xDC00..=0xDFFF => return self.error_at(start, "Lone surrogate"),
+            _ => high,
+        };
+        Ok(::std::char::from_u32(code).unwrap())
+    }
+
+    fn parse_quadhex(&mut self) -> Result<u32, JsonPathError> {
+        let digits = self.source[self.offset..].get(..4);
+        match digits.and_then(|d| u32::from_str_radix(d, 16).ok()) {
+            Some(code) if digits.unwrap().chars().all(|c| c.is_ascii_hexdigit()) => {
+                self.offset += 4;
+                Ok(code)
+            }
+            _ => self.error("Expected 4 hex digits"),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, JsonPathError> {
+        let mut filters = vec![self.parse_and()?];
+        loop {
+            self.skip_ws();
+            if !self.eat_str("||") {
+                break;
+            }
+            self.skip_ws();
+            filters.push(self.parse_and()?);
+        }
+        Ok(if filters.len() == 1 {
+            filters.pop().unwrap()
+        } else {
+            Filter::Or(filters)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, JsonPathError> {
+        let mut filters = vec![self.parse_basic()?];
+        loop {
+            self.skip_ws();
+            if !self.eat_str("&&") {
+                break;
+            }
+            self.skip_ws();
+            filters.push(self.parse_basic()?);
+        }
+        Ok(if filters.len() == 1 {
+            filters.pop().unwrap()
+        } else {
+            Filter::And(filters)
+        })
+    }
+
+    fn parse_basic(&mut self) -> Result<Filter, JsonPathError> {
+        if self.eat('!') {
+            self.skip_ws();
+            return Ok(Filter::Not(Box::new(self.parse_basic()?)));
+        }
+        if self.eat('(') {
+            self.skip_ws();
+            let filter = self.parse_or()?;
+            self.skip_ws();
+            self.expect(')')?;
+            return Ok(filter);
+        }
+
+        let start = self.offset;
+        let left = self.parse_operand()?;
+        let offset = self.offset;
+        self.skip_ws();
+        let op = match self.parse_compare_op() {
+            Some(op) => op,
+            None => {
+                self.offset = offset;
+                return match left {
+                    Operand::Query(query) => Ok(Filter::Exists(query)),
+                    Operand::Match(m) => Ok(Filter::Match(Box::new(m))),
+                    Operand::Comparable(_) => self.error_at(start, "Expected a comparison"),
+                };
+            }
+        };
+
+        let left = self.to_comparable(left, start)?;
+        self.skip_ws();
+        let start = self.offset;
+        let right = self.parse_operand()?;
+        let right = self.to_comparable(right, start)?;
+        Ok(Filter::Compare(left, op, right))
+    }
+
+    fn parse_compare_op(&mut self) -> Option<CompareOp> {
+        let ops = [
+            ("==", CompareOp::Eq),
+            ("!=", CompareOp::Ne),
+            ("<=", CompareOp::Le),
+            (">=", CompareOp::Ge),
+            ("<", CompareOp::Lt),
+            (">", CompareOp::Gt),
+        ];
+        ops.iter()
+            .find(|&&(token, _)| self.eat_str(token))
+            .map(|&(_, op)| op)
+    }
+
+    fn to_comparable(&self, operand: Operand, start: usize) -> Result<Comparable, JsonPathError> {
+        match operand {
+            Operand::Comparable(c) => Ok(c),
+            Operand::Query(ref query) if !query.is_singular() => {
+                self.error_at(start, "Expected a singular query")
+            }
+            Operand::Query(query) => Ok(Comparable::Query(query)),
+            Operand::Match(_) => self.error_at(start, "Expected a value rather than a logical"),
+        }
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, JsonPathError> {
+        let start = self.offset;
+        match self.peek() {
+            Some(c @ '@') | Some(c @ '$') => {
+                self.bump();
+                let segments = self.parse_segments()?;
+                Ok(Operand::Query(Query {
+                    relative: c == '@',
+                    segments,
+                }))
+            }
+            Some('\'') | Some('"') => Ok(Operand::Comparable(Comparable::Literal(Value::String(
+                self.parse_string()?,
+            )))),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(c) if c.is_ascii_lowercase() => {
+                while let Some(c) = self.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    self.offset += 1;
+                }
+                let name = &self.source[start..self.offset];
+                let literal = match name {
+                    "true" => Value::Boolean(true),
+                    "false" => Value::Boolean(false),
+                    "null" => Value::Null,
+                    _ => return self.parse_function(name, start),
+                };
+                Ok(Operand::Comparable(Comparable::Literal(literal)))
+            }
+            _ => self.error("Expected a query, literal or function"),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Operand, JsonPathError> {
+        let start = self.offset;
+        self.eat('-');
+        let mut prev = None;
+        while let Some(c) = self.peek() {
+            let is_sign = (c == '+' || c == '-') && (prev == Some('e') || prev == Some('E'));
+            if !(c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || is_sign) {
+                break;
+            }
+            prev = Some(c);
+            self.offset += 1;
+        }
+
+        let text = &self.source[start..self.offset];
+        match Number::parse(text, false) {
+            Some(number) => Ok(Operand::Comparable(Comparable::Literal(Value::Number(
+                number,
+            )))),
+            None => self.error_at(start, "Invalid number"),
+        }
+    }
+
+    fn parse_function(&mut self, name: &str, start: usize) -> Result<Operand, JsonPathError> {
+        self.expect('(')?;
+        self.skip_ws();
+        let operand = match name {
+            "length" => {
+                let arg_start = self.offset;
+                let arg = self.parse_operand()?;
+                let arg = self.to_comparable(arg, arg_start)?;
+                Operand::Comparable(Comparable::Length(Box::new(arg)))
+            }
+            "count" => Operand::Comparable(Comparable::Count(self.parse_query_arg()?)),
+            "value" => Operand::Comparable(Comparable::Value(self.parse_query_arg()?)),
+            "match" | "search" if !cfg!(feature = "regex") => {
+                return self.error_at(start, &format!("{}() requires `regex` feature", name));
+            }
+            "match" | "search" => {
+                let whole = name == "match";
+                let value_start = self.offset;
+                let value = self.parse_operand()?;
+                let value = self.to_comparable(value, value_start)?;
+                self.skip_ws();
+                self.expect(',')?;
+                self.skip_ws();
+                let pattern_start = self.offset;
+                let pattern = self.parse_operand()?;
+                let pattern = self.to_comparable(pattern, pattern_start)?;
+                let regex = match pattern {
+                    Comparable::Literal(Value::String(ref pattern)) => {
+                        match compile_regex(pattern, whole) {
+                            Some(regex) => Some(regex),
+                            None => return self.error_at(pattern_start, "Invalid regex"),
+                        }
+                    }
+                    _ => None,
+                };
+                Operand::Match(RegexMatch {
+                    value,
+                    pattern,
+                    regex,
+                    whole,
+                })
+            }
+            _ => return self.error_at(start, &format!("Unknown function {:?}", name)),
+        };
+        self.skip_ws();
+        self.expect(')')?;
+        Ok(operand)
+    }
+
+    fn parse_query_arg(&mut self) -> Result<Query, JsonPathError> {
+        match self.parse_operand()? {
+            Operand::Query(query) => Ok(query),
+            _ => self.error("Expected a query"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_string;
+
+    fn store() -> Value {
+        parse_string(
+            r#"{ "store": {
+                "book": [
+                    { "category": "reference", "author": "Nigel Rees",
+                      "title": "Sayings of the Century", "price": 8.95 },
+                    { "category": "fiction", "author": "Evelyn Waugh",
+                      "title": "Sword of Honour", "price": 12.99 },
+                    { "category": "fiction", "author": "Herman Melville",
+                      "title": "Moby Dick", "isbn": "0-553-21311-3", "price": 8.99 },
+                    { "category": "fiction", "author": "J. R. R. Tolkien",
+                      "title": "The Lord of the Rings", "isbn": "0-395-19395-8", "price": 22.99 }
+                ],
+                "bicycle": { "color": "red", "price": 399 }
+            } }"#,
+        )
+        .unwrap()
+    }
+
+    fn query(value: &Value, path: &str) -> Vec<String> {
+        value
+            .query(path)
+            .unwrap()
+            .into_iter()
+            .map(|v| v.serialize())
+            .collect()
+    }
+
+    fn sorted(mut items: Vec<String>) -> Vec<String> {
+        items.sort();
+        items
+    }
+
+    #[test]
+    fn test_query_rfc_examples() {
+        let value = store();
+        let authors = vec![
+            r#""Nigel Rees""#,
+            r#""Evelyn Waugh""#,
+            r#""Herman Melville""#,
+            r#""J. R. R. Tolkien""#,
+        ];
+        assert_eq!(query(&value, "$.store.book[*].author"), authors);
+        assert_eq!(query(&value, "$..author"), authors);
+        assert_eq!(
+            sorted(query(&value, "$.store..price")),
+            vec!["12.99", "22.99", "399", "8.95", "8.99"]
+        );
+        assert_eq!(
+            query(&value, "$..book[2].author"),
+            vec![r#""Herman Melville""#]
+        );
+        assert_eq!(
+            query(&value, "$..book[-1].title"),
+            vec![r#""The Lord of the Rings""#]
+        );
+        assert_eq!(
+            query(&value, "$..book[0,1].title"),
+            query(&value, "$..book[:2].title")
+        );
+        assert_eq!(
+            query(&value, "$..book[?@.isbn].title"),
+            vec![r#""Moby Dick""#, r#""The Lord of the Rings""#]
+        );
+        assert_eq!(
+            query(&value, "$.store.book[?(@.price < 10)].title"),
+            vec![r#""Sayings of the Century""#, r#""Moby Dick""#]
+        );
+        assert_eq!(query(&value, "$..*").len(), 27);
+        assert_eq!(value.query("$").unwrap(), vec![&value]);
+    }
+
+    #[test]
+    fn test_query_selectors() {
+        let value = parse_string(r#"{"a": [0, 1, 2, 3, 4, 5, 6], "k'\"": 7, "ü": 8}"#).unwrap();
+        assert_eq!(query(&value, "$.a[1:3]"), vec!["1", "2"]);
+        assert_eq!(query(&value, "$.a[5:]"), vec!["5", "6"]);
+        assert_eq!(query(&value, "$.a[1:5:2]"), vec!["1", "3"]);
+        assert_eq!(query(&value, "$.a[5:1:-2]"), vec!["5", "3"]);
+        assert_eq!(query(&value, "$.a[::-3]"), vec!["6", "3", "0"]);
+        assert_eq!(query(&value, "$.a[-2:]"), vec!["5", "6"]);
+        assert!(query(&value, "$.a[::0]").is_empty());
+        assert!(query(&value, "$.a[7]").is_empty());
+        assert_eq!(query(&value, "$.a[0, -1, 0]"), vec!["0", "6", "0"]);
+        assert_eq!(query(&value, r#"$['k\'"']"#), vec!["7"]);
+        assert_eq!(query(&value, r#"$["ü"]"#), vec!["8"]);
+        assert_eq!(query(&value, "$.ü"), vec!["8"]);
+        assert!(query(&value, "$.missing.x").is_empty());
+    }
+
+    #[test]
+    fn test_query_filters() {
+        let value = store();
+        let titles = |filter: &str| query(&value, &format!("$.store.book[?{}].title", filter));
+
+        assert_eq!(titles("@.price > 20"), vec![r#""The Lord of the Rings""#]);
+        assert_eq!(
+            titles("@.category == 'fiction' && !(@.price >= 12)"),
+            vec![r#""Moby Dick""#]
+        );
+        assert_eq!(titles("@.price < 9 || @.price > 20").len(), 3);
+        assert_eq!(titles("@.missing == @.other").len(), 4);
+        assert!(titles("@.missing < 1").is_empty());
+        assert_eq!(
+            titles("@.price < $.store.bicycle.price && @.price > 20"),
+            vec![r#""The Lord of the Rings""#]
+        );
+        assert_eq!(titles("length(@.title) == 9"), vec![r#""Moby Dick""#]);
+        assert_eq!(titles("count(@.*) == 5").len(), 2);
+        assert_eq!(titles("value(@..isbn) == '0-553-21311-3'").len(), 1);
+
+        let value = parse_string(r#"[1, 1.0, "1", true, null, [1], {"a": 1}]"#).unwrap();
+        assert_eq!(query(&value, "$[?@ == 1]"), vec!["1", "1"]);
+        assert_eq!(query(&value, "$[?@ == null]"), vec!["null"]);
+        assert_eq!(
+            query(&value, "$[?@[0] == 1 || @.a == 1]"),
+            vec!["[1]", r#"{"a":1}"#]
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_query_match_and_search() {
+        let value = store();
+        let titles = |filter: &str| query(&value, &format!("$.store.book[?{}].title", filter));
+
+        assert_eq!(
+            titles("match(@.author, 'J.*')"),
+            vec![r#""The Lord of the Rings""#]
+        );
+        assert!(titles("match(@.author, 'Tolkien')").is_empty());
+        assert_eq!(titles("search(@.author, 'Tolkien')").len(), 1);
+        assert_eq!(titles("search(@.author, @.category)").len(), 0);
+
+        let err = JsonPath::parse("$[?match(@, '(')]").expect_err("Expected an error.");
+        assert_eq!((err.offset(), err.message()), (12, "Invalid regex"));
+    }
+
+    #[cfg(not(feature = "regex"))]
+    #[test]
+    fn test_query_match_requires_regex() {
+        let err = JsonPath::parse("$[?search(@, 'a')]").expect_err("Expected an error.");
+        assert_eq!(
+            (err.offset(), err.message()),
+            (3, "search() requires `regex` feature")
+        );
+    }
+
+    #[test]
+    fn test_query_errors() {
+        fn error(path: &str) -> (usize, String) {
+            let err = JsonPath::parse(path).expect_err("Expected an error.");
+            (err.offset(), err.message().to_string())
+        }
+
+        assert_eq!(error("a"), (0, "Expected '$'".to_string()));
+        assert_eq!(error("$a"), (1, "Expected '.' or '['".to_string()));
+        assert_eq!(
+            error("$."),
+            (2, "Expected a member name or '*'".to_string())
+        );
+        assert_eq!(error("$[1"), (3, "Expected ']'".to_string()));
+        assert_eq!(error("$[01]"), (2, "Invalid integer".to_string()));
+        assert_eq!(error("$['a]"), (2, "Unterminated string".to_string()));
+        assert_eq!(
+            error("$[?@.* == 1]"),
+            (3, "Expected a singular query".to_string())
+        );
+        assert_eq!(error("$[?1]"), (3, "Expected a comparison".to_string()));
+        assert_eq!(
+            error("$[?foo(@)]"),
+            (3, "Unknown function \"foo\"".to_string())
+        );
+        assert_eq!(
+            JsonPath::parse("$[").unwrap_err().to_string(),
+            "Expected a selector at offset 2"
+        );
+    }
+}
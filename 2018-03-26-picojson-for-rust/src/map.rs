@@ -0,0 +1,308 @@
+//! Map of JSON object members.
+//!
+//! Keys are sorted by default. With `preserve_order` feature,
+//! keys are kept in insertion order instead, so that parse → serialize round-trips.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::Hash;
+use std::iter::FromIterator;
+use std::ops;
+
+use Value;
+
+#[cfg(not(feature = "preserve_order"))]
+use std::collections::btree_map as imp;
+#[cfg(not(feature = "preserve_order"))]
+type MapImpl = imp::BTreeMap<String, Value>;
+
+#[cfg(feature = "preserve_order")]
+use indexmap::map as imp;
+#[cfg(feature = "preserve_order")]
+type MapImpl = imp::IndexMap<String, Value>;
+
+/// Represents a JSON object, a map from strings to values.
+#[derive(Clone, Default, PartialEq)]
+pub struct Map {
+    map: MapImpl,
+}
+
+impl Map {
+    pub fn new() -> Map {
+        Map {
+            map: MapImpl::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        String: Borrow<Q>,
+        Q: ?Sized + Ord + Eq + Hash,
+    {
+        self.map.contains_key(key)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&Value>
+    where
+        String: Borrow<Q>,
+        Q: ?Sized + Ord + Eq + Hash,
+    {
+        self.map.get(key)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut Value>
+    where
+        String: Borrow<Q>,
+        Q: ?Sized + Ord + Eq + Hash,
+    {
+        self.map.get_mut(key)
+    }
+
+    /// Inserts a member and returns the previous value.
+    /// An existing key keeps its position.
+    pub fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        self.map.insert(key, value)
+    }
+
+    /// Removes a member. The order of the remaining members is kept.
+    #[cfg(not(feature = "preserve_order"))]
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<Value>
+    where
+        String: Borrow<Q>,
+        Q: ?Sized + Ord + Eq + Hash,
+    {
+        self.map.remove(key)
+    }
+
+    /// Removes a member. The order of the remaining members is kept.
+    #[cfg(feature = "preserve_order")]
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<Value>
+    where
+        String: Borrow<Q>,
+        Q: ?Sized + Ord + Eq + Hash,
+    {
+        self.map.shift_remove(key)
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.map.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        IterMut(self.map.iter_mut())
+    }
+
+    pub fn keys(&self) -> Keys<'_> {
+        Keys(self.map.keys())
+    }
+
+    pub fn values(&self) -> Values<'_> {
+        Values(self.map.values())
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_> {
+        ValuesMut(self.map.values_mut())
+    }
+
+    /// Sorts the members by key.
+    #[cfg(feature = "preserve_order")]
+    pub fn sort_keys(&mut self) {
+        self.map.sort_keys()
+    }
+}
+
+impl fmt::Debug for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Compares members in key order, regardless of the insertion order.
+impl PartialOrd for Map {
+    fn partial_cmp(&self, other: &Map) -> Option<Ordering> {
+        fn sorted(map: &Map) -> Vec<(&String, &Value)> {
+            let mut members = map.iter().collect::<Vec<_>>();
+            members.sort_by(|l, r| l.0.cmp(r.0));
+            members
+        }
+
+        sorted(self).partial_cmp(&sorted(other))
+    }
+}
+
+impl<Q> ops::Index<&Q> for Map
+where
+    String: Borrow<Q>,
+    Q: ?Sized + Ord + Eq + Hash,
+{
+    type Output = Value;
+
+    fn index(&self, key: &Q) -> &Value {
+        self.map.index(key)
+    }
+}
+
+impl FromIterator<(String, Value)> for Map {
+    fn from_iter<I: IntoIterator<Item = (String, Value)>>(iter: I) -> Map {
+        Map {
+            map: MapImpl::from_iter(iter),
+        }
+    }
+}
+
+impl Extend<(String, Value)> for Map {
+    fn extend<I: IntoIterator<Item = (String, Value)>>(&mut self, iter: I) {
+        self.map.extend(iter)
+    }
+}
+
+macro_rules! delegate_iterator {
+    ($name:ident $(<$lt:lifetime>)*, $inner:ty, $item:ty) => {
+        pub struct $name $(<$lt>)* ($inner);
+
+        impl $(<$lt>)* Iterator for $name $(<$lt>)* {
+            type Item = $item;
+
+            fn next(&mut self) -> Option<$item> {
+                self.0.next()
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.0.size_hint()
+            }
+        }
+
+        impl $(<$lt>)* DoubleEndedIterator for $name $(<$lt>)* {
+            fn next_back(&mut self) -> Option<$item> {
+                self.0.next_back()
+            }
+        }
+
+        impl $(<$lt>)* ExactSizeIterator for $name $(<$lt>)* {
+            fn len(&self) -> usize {
+                self.0.len()
+            }
+        }
+    };
+}
+
+delegate_iterator!(
+    Iter<'a>,
+    imp::Iter<'a, String, Value>,
+    (&'a String, &'a Value)
+);
+delegate_iterator!(
+    IterMut<'a>,
+    imp::IterMut<'a, String, Value>,
+    (&'a String, &'a mut Value)
+);
+delegate_iterator!(IntoIter, imp::IntoIter<String, Value>, (String, Value));
+delegate_iterator!(Keys<'a>, imp::Keys<'a, String, Value>, &'a String);
+delegate_iterator!(Values<'a>, imp::Values<'a, String, Value>, &'a Value);
+delegate_iterator!(
+    ValuesMut<'a>,
+    imp::ValuesMut<'a, String, Value>,
+    &'a mut Value
+);
+
+impl IntoIterator for Map {
+    type Item = (String, Value);
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        IntoIter(self.map.into_iter())
+    }
+}
+
+impl<'a> IntoIterator for &'a Map {
+    type Item = (&'a String, &'a Value);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Map {
+    type Item = (&'a String, &'a mut Value);
+    type IntoIter = IterMut<'a>;
+
+    fn into_iter(self) -> IterMut<'a> {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(keys: &[&str]) -> Map {
+        keys.iter()
+            .enumerate()
+            .map(|(i, k)| (k.to_string(), Value::from(i as i64)))
+            .collect()
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut m = map(&["b", "a"]);
+        assert_eq!(m.len(), 2);
+        assert_eq!(m["a"], Value::from(1_i64));
+        assert_eq!(
+            m.insert("a".to_string(), Value::Null),
+            Some(Value::from(1_i64))
+        );
+        assert_eq!(m.remove("b"), Some(Value::from(0_i64)));
+        assert!(!m.contains_key("b"));
+        assert_eq!(m.keys().collect::<Vec<_>>(), vec!["a"]);
+    }
+
+    #[test]
+    fn test_eq_ignores_order() {
+        let l = vec![("a", 1), ("b", 2)].into_iter();
+        let r = vec![("b", 2), ("a", 1)].into_iter();
+        let l = l
+            .map(|(k, v)| (k.to_string(), Value::from(v as i64)))
+            .collect::<Map>();
+        let r = r
+            .map(|(k, v)| (k.to_string(), Value::from(v as i64)))
+            .collect::<Map>();
+        assert_eq!(l, r);
+        assert_eq!(l.partial_cmp(&r), Some(Ordering::Equal));
+    }
+
+    #[cfg(not(feature = "preserve_order"))]
+    #[test]
+    fn test_sorted() {
+        let m = map(&["c", "a", "b"]);
+        assert_eq!(m.keys().collect::<Vec<_>>(), vec!["a", "b", "c"]);
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[test]
+    fn test_preserve_order() {
+        let mut m = map(&["c", "a", "b"]);
+        assert_eq!(m.keys().collect::<Vec<_>>(), vec!["c", "a", "b"]);
+
+        m.remove("a");
+        m.insert("c".to_string(), Value::Null);
+        m.insert("d".to_string(), Value::Null);
+        assert_eq!(m.keys().collect::<Vec<_>>(), vec!["c", "b", "d"]);
+
+        m.sort_keys();
+        assert_eq!(m.keys().collect::<Vec<_>>(), vec!["b", "c", "d"]);
+    }
+}
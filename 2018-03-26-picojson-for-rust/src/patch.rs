@@ -0,0 +1,421 @@
+//! JSON Patch ([RFC 6902](https://tools.ietf.org/html/rfc6902))
+//! and JSON Merge Patch ([RFC 7396](https://tools.ietf.org/html/rfc7396)).
+
+use std::fmt;
+
+use pointer::{Pointer, PointerError};
+use Value;
+
+/// Represents a reason why a patch can't be applied.
+/// `index` is the position of the failed operation in the patch.
+#[derive(PartialEq, Clone, Debug)]
+pub enum PatchError {
+    /// The patch isn't an array of operation objects.
+    InvalidPatch(String),
+    /// The operation is malformed, e.g., lacks a member.
+    InvalidOperation { index: usize, message: String },
+    /// A pointer in the operation can't be resolved.
+    Pointer { index: usize, error: PointerError },
+    /// `move` tries to move a value into its own child.
+    MoveIntoChild { index: usize },
+    /// `test` found a different value.
+    TestFailed { index: usize, path: String },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatchError::InvalidPatch(ref message) => write!(f, "Invalid patch: {}", message),
+            PatchError::InvalidOperation { index, ref message } => {
+                write!(f, "Invalid operation at {}: {}", index, message)
+            }
+            PatchError::Pointer { index, ref error } => {
+                write!(f, "Operation at {} failed: {}", index, error)
+            }
+            PatchError::MoveIntoChild { index } => write!(
+                f,
+                "Operation at {} failed: Can't move a value into its child",
+                index
+            ),
+            PatchError::TestFailed { index, ref path } => {
+                write!(
+                    f,
+                    "Operation at {} failed: Test at {:?} failed",
+                    index, path
+                )
+            }
+        }
+    }
+}
+
+impl ::std::error::Error for PatchError {}
+
+enum Operation {
+    Add { path: Pointer, value: Value },
+    Remove { path: Pointer },
+    Replace { path: Pointer, value: Value },
+    Move { from: Pointer, path: Pointer },
+    Copy { from: Pointer, path: Pointer },
+    Test { path: Pointer, value: Value },
+}
+
+fn parse_operation(index: usize, op: &Value) -> Result<Operation, PatchError> {
+    let invalid = |message: String| PatchError::InvalidOperation { index, message };
+
+    if !op.is_of::<::Object>() {
+        return Err(invalid("Operation must be an object".to_string()));
+    }
+
+    let pointer = |name: &str| -> Result<Pointer, PatchError> {
+        let text = op
+            .get(name)
+            .and_then(|v| v.as_string())
+            .ok_or_else(|| invalid(format!("Missing string member {:?}", name)))?;
+        Pointer::parse(text).map_err(|error| PatchError::Pointer { index, error })
+    };
+
+    let value = || -> Result<Value, PatchError> {
+        op.get("value")
+            .cloned()
+            .ok_or_else(|| invalid("Missing member \"value\"".to_string()))
+    };
+
+    let name = op
+        .get("op")
+        .and_then(|v| v.as_string())
+        .ok_or_else(|| invalid("Missing string member \"op\"".to_string()))?;
+    match name.as_str() {
+        "add" => Ok(Operation::Add {
+            path: pointer("path")?,
+            value: value()?,
+        }),
+        "remove" => Ok(Operation::Remove {
+            path: pointer("path")?,
+        }),
+        "replace" => Ok(Operation::Replace {
+            path: pointer("path")?,
+            value: value()?,
+        }),
+        "move" => Ok(Operation::Move {
+            from: pointer("from")?,
+            path: pointer("path")?,
+        }),
+        "copy" => Ok(Operation::Copy {
+            from: pointer("from")?,
+            path: pointer("path")?,
+        }),
+        "test" => Ok(Operation::Test {
+            path: pointer("path")?,
+            value: value()?,
+        }),
+        _ => Err(invalid(format!("Unknown operation {:?}", name))),
+    }
+}
+
+fn apply_operation(target: &mut Value, index: usize, op: Operation) -> Result<(), PatchError> {
+    let pointer_error = |error| PatchError::Pointer { index, error };
+
+    match op {
+        Operation::Add { path, value } => {
+            target
+                .insert_by_pointer(&path, value)
+                .map_err(pointer_error)?;
+        }
+        Operation::Remove { path } => {
+            target.remove_by_pointer(&path).map_err(pointer_error)?;
+        }
+        Operation::Replace { path, value } => {
+            *target.get_by_pointer_mut(&path).map_err(pointer_error)? = value;
+        }
+        Operation::Move { from, path } => {
+            if from == path {
+                // Still the source must exist.
+                target.get_by_pointer(&from).map_err(pointer_error)?;
+                return Ok(());
+            }
+            if from.is_ancestor_of(&path) {
+                return Err(PatchError::MoveIntoChild { index });
+            }
+
+            let value = target.remove_by_pointer(&from).map_err(pointer_error)?;
+            target
+                .insert_by_pointer(&path, value)
+                .map_err(pointer_error)?;
+        }
+        Operation::Copy { from, path } => {
+            let value = target.get_by_pointer(&from).map_err(pointer_error)?.clone();
+            target
+                .insert_by_pointer(&path, value)
+                .map_err(pointer_error)?;
+        }
+        Operation::Test { path, value } => {
+            if *target.get_by_pointer(&path).map_err(pointer_error)? != value {
+                return Err(PatchError::TestFailed {
+                    index,
+                    path: path.to_string(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Applies a JSON Patch to the target.
+/// The patch is applied atomically: if any operation fails, the target is left unchanged.
+pub fn apply_patch(target: &mut Value, patch: &Value) -> Result<(), PatchError> {
+    let ops = patch
+        .as_array()
+        .ok_or_else(|| PatchError::InvalidPatch("Patch must be an array".to_string()))?;
+
+    let mut patched = target.clone();
+    for (index, op) in ops.iter().enumerate() {
+        let op = parse_operation(index, op)?;
+        apply_operation(&mut patched, index, op)?;
+    }
+
+    *target = patched;
+    Ok(())
+}
+
+/// Applies a JSON Merge Patch to the target.
+pub fn apply_merge_patch(target: &mut Value, patch: &Value) {
+    let patch = match *patch {
+        Value::Object(ref patch) => patch,
+        _ => {
+            *target = patch.clone();
+            return;
+        }
+    };
+
+    if !target.is_of::<::Object>() {
+        *target = Value::object();
+    }
+
+    for (key, value) in patch.iter() {
+        if value.is_null() {
+            target.remove(key.as_str());
+            continue;
+        }
+
+        if !target.has(key.as_str()) {
+            target.insert(key.as_str(), Value::Null);
+        }
+        apply_merge_patch(target.get_mut(key.as_str()).unwrap(), value);
+    }
+}
+
+fn make_op(op: &str, path: &Pointer, value: Option<&Value>) -> Value {
+    let mut items = vec![
+        ("op", Value::from(op)),
+        ("path", Value::from(path.to_string())),
+    ];
+    if let Some(value) = value {
+        items.push(("value", value.clone()));
+    }
+    Value::from(items)
+}
+
+fn diff_core(from: &Value, to: &Value, path: &Pointer, ops: &mut Vec<Value>) {
+    if from == to {
+        return;
+    }
+
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            for key in from.keys() {
+                if !to.contains_key(key) {
+                    ops.push(make_op("remove", &path.join(key), None));
+                }
+            }
+            for (key, value) in to.iter() {
+                match from.get(key) {
+                    Some(old) => diff_core(old, value, &path.join(key), ops),
+                    None => ops.push(make_op("add", &path.join(key), Some(value))),
+                }
+            }
+        }
+        (Value::Array(from), Value::Array(to)) => {
+            // Skip common prefix and suffix so that an insertion or deletion is a single op.
+            let prefix = from
+                .iter()
+                .zip(to.iter())
+                .take_while(|&(l, r)| l == r)
+                .count();
+            let suffix = from[prefix..]
+                .iter()
+                .rev()
+                .zip(to[prefix..].iter().rev())
+                .take_while(|&(l, r)| l == r)
+                .count();
+            let from_mid = &from[prefix..from.len() - suffix];
+            let to_mid = &to[prefix..to.len() - suffix];
+
+            let common = from_mid.len().min(to_mid.len());
+            for i in 0..common {
+                diff_core(&from_mid[i], &to_mid[i], &path.join(prefix + i), ops);
+            }
+
+            // Remove from the back so that indexes of the rest don't shift.
+            for i in (common..from_mid.len()).rev() {
+                ops.push(make_op("remove", &path.join(prefix + i), None));
+            }
+
+            for (i, value) in to_mid.iter().enumerate().skip(common) {
+                ops.push(make_op("add", &path.join(prefix + i), Some(value)));
+            }
+        }
+        _ => ops.push(make_op("replace", path, Some(to))),
+    }
+}
+
+/// Computes a JSON Patch which transforms `from` into `to`.
+pub fn diff(from: &Value, to: &Value) -> Value {
+    let mut ops = Vec::new();
+    diff_core(from, to, &Pointer::root(), &mut ops);
+    Value::Array(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_string;
+
+    fn json(source: &str) -> Value {
+        parse_string(source).unwrap()
+    }
+
+    fn patched(target: &str, patch: &str) -> Result<Value, PatchError> {
+        let mut target = json(target);
+        apply_patch(&mut target, &json(patch)).map(|()| target)
+    }
+
+    #[test]
+    fn test_apply_patch_operations() {
+        let target = r#"{ "a": { "b": [1, 2] }, "c": "x" }"#;
+        let patch = r#"[
+            { "op": "add", "path": "/a/b/1", "value": 9 },
+            { "op": "remove", "path": "/c" },
+            { "op": "replace", "path": "/a/b/0", "value": "one" },
+            { "op": "copy", "from": "/a/b", "path": "/d" },
+            { "op": "move", "from": "/a/b/2", "path": "/e" },
+            { "op": "test", "path": "/d/1", "value": 9 }
+        ]"#;
+        assert_eq!(
+            patched(target, patch),
+            Ok(json(
+                r#"{ "a": { "b": ["one", 9] }, "d": ["one", 9, 2], "e": 2 }"#
+            ))
+        );
+    }
+
+    #[test]
+    fn test_apply_patch_is_atomic() {
+        let mut target = json(r#"{ "a": 1 }"#);
+        let patch = json(
+            r#"[
+            { "op": "add", "path": "/b", "value": 2 },
+            { "op": "test", "path": "/a", "value": 2 }
+        ]"#,
+        );
+        assert_eq!(
+            apply_patch(&mut target, &patch),
+            Err(PatchError::TestFailed {
+                index: 1,
+                path: "/a".to_string(),
+            })
+        );
+        assert_eq!(target, json(r#"{ "a": 1 }"#));
+    }
+
+    #[test]
+    fn test_apply_patch_errors() {
+        assert_eq!(
+            patched("{}", r#"[{ "op": "remove", "path": "/x" }]"#),
+            Err(PatchError::Pointer {
+                index: 0,
+                error: PointerError::NotFound {
+                    path: "/x".to_string(),
+                },
+            })
+        );
+        assert_eq!(
+            patched(
+                r#"{ "a": { "b": 1 } }"#,
+                r#"[{ "op": "move", "from": "/a", "path": "/a/c" }]"#
+            ),
+            Err(PatchError::MoveIntoChild { index: 0 })
+        );
+        assert!(patched("{}", r#"[{ "op": "frobnicate", "path": "" }]"#).is_err());
+        assert!(patched("{}", r#"[{ "op": "add", "path": "/a" }]"#).is_err());
+        assert!(patched("{}", r#"{ "op": "add" }"#).is_err());
+    }
+
+    #[test]
+    fn test_apply_merge_patch() {
+        let mut target = json(
+            r#"{ "title": "Goodbye!", "author": { "givenName": "John", "familyName": "Doe" },
+            "tags": ["example", "sample"], "content": "This will be unchanged" }"#,
+        );
+        let patch = json(
+            r#"{ "title": "Hello!", "phoneNumber": "+01-123-456-7890",
+            "author": { "familyName": null }, "tags": ["example"] }"#,
+        );
+        apply_merge_patch(&mut target, &patch);
+        assert_eq!(
+            target,
+            json(
+                r#"{ "title": "Hello!", "author": { "givenName": "John" },
+                "tags": ["example"], "content": "This will be unchanged",
+                "phoneNumber": "+01-123-456-7890" }"#
+            )
+        );
+
+        let mut target = json(r#"["a"]"#);
+        apply_merge_patch(&mut target, &json(r#"{ "a": { "b": null, "c": 1 } }"#));
+        assert_eq!(target, json(r#"{ "a": { "c": 1 } }"#));
+    }
+
+    #[test]
+    fn test_diff() {
+        let from = json(r#"{ "a": [1, 2, 3, 4], "b": { "c": true }, "d": 1 }"#);
+        let to = json(r#"{ "a": [1, 5, 2, 3, 4], "b": { "c": false }, "e": null }"#);
+        let patch = diff(&from, &to);
+        assert_eq!(
+            patch,
+            json(
+                r#"[
+                { "op": "remove", "path": "/d" },
+                { "op": "add", "path": "/a/1", "value": 5 },
+                { "op": "replace", "path": "/b/c", "value": false },
+                { "op": "add", "path": "/e", "value": null }
+            ]"#
+            )
+        );
+
+        let mut target = from.clone();
+        apply_patch(&mut target, &patch).unwrap();
+        assert_eq!(target, to);
+    }
+
+    #[test]
+    fn test_diff_roundtrip() {
+        let table = vec![
+            ("[1, 2, 3]", "[3]"),
+            ("[1, 2, 3]", "[0, 1, 2, 3, 4]"),
+            (
+                r#"{ "x": [1, { "y": 2 }] }"#,
+                r#"{ "x": [1, { "y": 3, "z": 4 }] }"#,
+            ),
+            ("1", r#""one""#),
+            ("[]", "{}"),
+        ];
+        for (from, to) in table {
+            let (from, to) = (json(from), json(to));
+            let mut target = from.clone();
+            apply_patch(&mut target, &diff(&from, &to)).unwrap();
+            assert_eq!(target, to);
+        }
+
+        assert_eq!(diff(&json("[1, 2]"), &json("[1, 2]")), Value::array());
+    }
+}
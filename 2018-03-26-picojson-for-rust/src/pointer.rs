@@ -0,0 +1,408 @@
+//! JSON Pointer ([RFC 6901](https://tools.ietf.org/html/rfc6901)).
+
+use std::fmt;
+
+use {Value, ValueKey};
+
+/// Represents a reason why a pointer can't be resolved.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum PointerError {
+    /// The pointer is neither empty nor starts with `/`.
+    InvalidSyntax(String),
+    /// Found `~` not followed by `0` or `1`.
+    InvalidEscape(String),
+    /// The parent is an array but the token isn't an index.
+    InvalidIndex { path: String, token: String },
+    /// The parent is neither an array nor an object.
+    NotContainer { path: String },
+    /// The parent has no item for the token.
+    NotFound { path: String },
+    /// The root can't be removed.
+    RemoveRoot,
+}
+
+impl fmt::Display for PointerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PointerError::InvalidSyntax(ref pointer) => {
+                write!(f, "Pointer must be empty or start with '/': {:?}", pointer)
+            }
+            PointerError::InvalidEscape(ref token) => {
+                write!(f, "Invalid escape sequence in {:?}", token)
+            }
+            PointerError::InvalidIndex {
+                ref path,
+                ref token,
+            } => write!(f, "Invalid array index {:?} at {:?}", token, path),
+            PointerError::NotContainer { ref path } => {
+                write!(f, "Value at {:?} is neither an array nor an object", path)
+            }
+            PointerError::NotFound { ref path } => write!(f, "No value at {:?}", path),
+            PointerError::RemoveRoot => write!(f, "Can't remove the root"),
+        }
+    }
+}
+
+impl ::std::error::Error for PointerError {}
+
+/// Represents a parsed JSON Pointer, i.e., a list of unescaped reference tokens.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Debug, Default)]
+pub struct Pointer {
+    tokens: Vec<String>,
+}
+
+impl Pointer {
+    /// Creates a pointer to the root.
+    pub fn root() -> Pointer {
+        Pointer { tokens: Vec::new() }
+    }
+
+    /// Parses a pointer such as `/a/0/b~1c`.
+    pub fn parse(pointer: &str) -> Result<Pointer, PointerError> {
+        if pointer.is_empty() {
+            return Ok(Pointer::root());
+        }
+
+        if !pointer.starts_with('/') {
+            return Err(PointerError::InvalidSyntax(pointer.to_string()));
+        }
+
+        let tokens = pointer[1..]
+            .split('/')
+            .map(unescape_token)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Pointer { tokens })
+    }
+
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Appends a reference token.
+    pub fn push<T: ToString>(&mut self, token: T) {
+        self.tokens.push(token.to_string());
+    }
+
+    /// Creates a pointer with a reference token appended.
+    pub fn join<T: ToString>(&self, token: T) -> Pointer {
+        let mut pointer = self.clone();
+        pointer.push(token);
+        pointer
+    }
+
+    /// Splits into the pointer to the parent and the last token.
+    pub fn split_last(&self) -> Option<(Pointer, &str)> {
+        self.tokens.split_last().map(|(last, parent)| {
+            let parent = Pointer {
+                tokens: parent.to_vec(),
+            };
+            (parent, last.as_str())
+        })
+    }
+
+    /// Determines if the pointer is a proper prefix of the other.
+    pub fn is_ancestor_of(&self, other: &Pointer) -> bool {
+        self.tokens.len() < other.tokens.len() && other.tokens.starts_with(&self.tokens)
+    }
+
+    /// Formats the first `len` tokens as a pointer, for error messages.
+    fn prefix(&self, len: usize) -> String {
+        Pointer {
+            tokens: self.tokens[..len].to_vec(),
+        }
+        .to_string()
+    }
+}
+
+impl fmt::Display for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for token in &self.tokens {
+            write!(f, "/{}", token.replace('~', "~0").replace('/', "~1"))?;
+        }
+        Ok(())
+    }
+}
+
+fn unescape_token(token: &str) -> Result<String, PointerError> {
+    let mut out = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(c) = chars.next() {
+        if c != '~' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('0') => out.push('~'),
+            Some('1') => out.push('/'),
+            _ => return Err(PointerError::InvalidEscape(token.to_string())),
+        }
+    }
+    Ok(out)
+}
+
+/// Parses an array index. Leading zeros aren't allowed.
+fn parse_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() >= 2 && token.starts_with('0')) {
+        return None;
+    }
+    if !token.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// Where a token refers to in a collection.
+enum Slot<'a> {
+    Index(usize),
+    /// `-`, i.e., past the end of an array.
+    End,
+    Key(&'a str),
+}
+
+fn resolve_slot<'a>(
+    parent: &Value,
+    pointer: &Pointer,
+    depth: usize,
+    token: &'a str,
+) -> Result<Slot<'a>, PointerError> {
+    match *parent {
+        Value::Array(_) if token == "-" => Ok(Slot::End),
+        Value::Array(_) => {
+            parse_index(token)
+                .map(Slot::Index)
+                .ok_or_else(|| PointerError::InvalidIndex {
+                    path: pointer.prefix(depth),
+                    token: token.to_string(),
+                })
+        }
+        Value::Object(_) => Ok(Slot::Key(token)),
+        _ => Err(PointerError::NotContainer {
+            path: pointer.prefix(depth),
+        }),
+    }
+}
+
+impl Value {
+    /// Gets a value referred by the pointer.
+    pub fn pointer(&self, pointer: &str) -> Result<&Value, PointerError> {
+        self.get_by_pointer(&Pointer::parse(pointer)?)
+    }
+
+    /// Gets a mutable reference to a value referred by the pointer.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Result<&mut Value, PointerError> {
+        self.get_by_pointer_mut(&Pointer::parse(pointer)?)
+    }
+
+    /// Adds a value at the location referred by the pointer, as `add` operation of JSON Patch.
+    /// For an array, the value is inserted before the index, or appended if the token is `-`.
+    /// For an object, the member is added or replaced.
+    /// Returns the replaced value if any.
+    pub fn pointer_insert(
+        &mut self,
+        pointer: &str,
+        value: Value,
+    ) -> Result<Option<Value>, PointerError> {
+        self.insert_by_pointer(&Pointer::parse(pointer)?, value)
+    }
+
+    /// Removes a value referred by the pointer and returns it.
+    pub fn pointer_remove(&mut self, pointer: &str) -> Result<Value, PointerError> {
+        self.remove_by_pointer(&Pointer::parse(pointer)?)
+    }
+
+    pub fn get_by_pointer(&self, pointer: &Pointer) -> Result<&Value, PointerError> {
+        let mut value = self;
+        for (depth, token) in pointer.tokens.iter().enumerate() {
+            let key = match resolve_slot(value, pointer, depth, token)? {
+                Slot::Index(index) => ValueKey::Index(index),
+                Slot::Key(key) => ValueKey::Key(key),
+                Slot::End => ValueKey::Index(value.as_array().unwrap().len()),
+            };
+            value = value.get(key).ok_or_else(|| PointerError::NotFound {
+                path: pointer.prefix(depth + 1),
+            })?;
+        }
+        Ok(value)
+    }
+
+    pub fn get_by_pointer_mut(&mut self, pointer: &Pointer) -> Result<&mut Value, PointerError> {
+        let mut value = self;
+        for (depth, token) in pointer.tokens.iter().enumerate() {
+            let key = match resolve_slot(value, pointer, depth, token)? {
+                Slot::Index(index) => ValueKey::Index(index),
+                Slot::Key(key) => ValueKey::Key(key),
+                Slot::End => ValueKey::Index(value.as_array().unwrap().len()),
+            };
+            value = value.get_mut(key).ok_or_else(|| PointerError::NotFound {
+                path: pointer.prefix(depth + 1),
+            })?;
+        }
+        Ok(value)
+    }
+
+    pub fn insert_by_pointer(
+        &mut self,
+        pointer: &Pointer,
+        value: Value,
+    ) -> Result<Option<Value>, PointerError> {
+        let (parent_pointer, token) = match pointer.split_last() {
+            Some(it) => it,
+            None => return Ok(Some(::std::mem::replace(self, value))),
+        };
+
+        let depth = parent_pointer.tokens.len();
+        let parent = self.get_by_pointer_mut(&parent_pointer)?;
+        match resolve_slot(parent, pointer, depth, token)? {
+            Slot::Index(index) if index <= parent.as_array().unwrap().len() => {
+                parent.insert(index, value);
+                Ok(None)
+            }
+            Slot::Index(_) => Err(PointerError::NotFound {
+                path: pointer.to_string(),
+            }),
+            Slot::End => {
+                parent.push(value);
+                Ok(None)
+            }
+            Slot::Key(key) => Ok(parent.insert(key, value)),
+        }
+    }
+
+    pub fn remove_by_pointer(&mut self, pointer: &Pointer) -> Result<Value, PointerError> {
+        let (parent_pointer, token) = match pointer.split_last() {
+            Some(it) => it,
+            None => return Err(PointerError::RemoveRoot),
+        };
+
+        let depth = parent_pointer.tokens.len();
+        let parent = self.get_by_pointer_mut(&parent_pointer)?;
+        let removed = match resolve_slot(parent, pointer, depth, token)? {
+            Slot::Index(index) if index < parent.as_array().unwrap().len() => parent.remove(index),
+            Slot::Index(_) | Slot::End => None,
+            Slot::Key(key) => parent.remove(key),
+        };
+        removed.ok_or_else(|| PointerError::NotFound {
+            path: pointer.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_string;
+
+    fn sample() -> Value {
+        parse_string(
+            r#"{
+            "foo": ["bar", "baz"],
+            "": 0,
+            "a/b": 1,
+            "m~n": 8,
+            "nested": { "items": [{ "id": 1 }, { "id": 2 }] }
+        }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_pointer_rfc_examples() {
+        let value = sample();
+        assert_eq!(value.pointer(""), Ok(&value));
+        assert_eq!(value.pointer("/foo/0"), Ok(&Value::from("bar")));
+        assert_eq!(value.pointer("/"), Ok(&Value::from(0)));
+        assert_eq!(value.pointer("/a~1b"), Ok(&Value::from(1)));
+        assert_eq!(value.pointer("/m~0n"), Ok(&Value::from(8)));
+        assert_eq!(value.pointer("/nested/items/1/id"), Ok(&Value::from(2)));
+    }
+
+    #[test]
+    fn test_pointer_errors() {
+        let value = sample();
+        assert_eq!(
+            value.pointer("foo"),
+            Err(PointerError::InvalidSyntax("foo".to_string()))
+        );
+        assert_eq!(
+            value.pointer("/m~2n"),
+            Err(PointerError::InvalidEscape("m~2n".to_string()))
+        );
+        assert_eq!(
+            value.pointer("/foo/01"),
+            Err(PointerError::InvalidIndex {
+                path: "/foo".to_string(),
+                token: "01".to_string(),
+            })
+        );
+        assert_eq!(
+            value.pointer("/foo/2"),
+            Err(PointerError::NotFound {
+                path: "/foo/2".to_string(),
+            })
+        );
+        assert_eq!(
+            value.pointer("/foo/-"),
+            Err(PointerError::NotFound {
+                path: "/foo/-".to_string(),
+            })
+        );
+        assert_eq!(
+            value.pointer("/a~1b/x"),
+            Err(PointerError::NotContainer {
+                path: "/a~1b".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_pointer_mut() {
+        let mut value = sample();
+        *value.pointer_mut("/nested/items/0/id").unwrap() = Value::from("one");
+        assert_eq!(value.pointer("/nested/items/0/id"), Ok(&Value::from("one")));
+    }
+
+    #[test]
+    fn test_pointer_insert() {
+        let mut value = sample();
+        assert_eq!(value.pointer_insert("/foo/1", Value::from("qux")), Ok(None));
+        assert_eq!(value.pointer_insert("/foo/-", Value::from("end")), Ok(None));
+        assert_eq!(
+            value.pointer("/foo").unwrap().serialize(),
+            r#"["bar","qux","baz","end"]"#
+        );
+
+        assert_eq!(
+            value.pointer_insert("/m~0n", Value::Null),
+            Ok(Some(Value::from(8)))
+        );
+        assert!(value.pointer_insert("/foo/9", Value::Null).is_err());
+        assert!(value.pointer_insert("/missing/x", Value::Null).is_err());
+    }
+
+    #[test]
+    fn test_pointer_remove() {
+        let mut value = sample();
+        assert_eq!(value.pointer_remove("/foo/0"), Ok(Value::from("bar")));
+        assert_eq!(value.pointer_remove("/a~1b"), Ok(Value::from(1)));
+        assert!(!value.has("a/b"));
+        assert_eq!(
+            value.pointer_remove("/foo/5"),
+            Err(PointerError::NotFound {
+                path: "/foo/5".to_string(),
+            })
+        );
+        assert_eq!(value.pointer_remove(""), Err(PointerError::RemoveRoot));
+    }
+
+    #[test]
+    fn test_pointer_display() {
+        let pointer = Pointer::root().join("a/b").join("m~n").join(0);
+        assert_eq!(pointer.to_string(), "/a~1b/m~0n/0");
+        assert_eq!(Pointer::parse(&pointer.to_string()), Ok(pointer));
+    }
+}
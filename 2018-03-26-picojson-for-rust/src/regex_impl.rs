@@ -0,0 +1,45 @@
+//! Regular expressions for patterns in schemas and JSONPath, enabled by `regex` feature.
+//!
+//! Without the feature, every pattern fails to compile, so that everything else still works.
+
+#[cfg(feature = "regex")]
+pub use regex::Regex;
+
+#[cfg(not(feature = "regex"))]
+pub use self::disabled::Regex;
+
+#[cfg(not(feature = "regex"))]
+mod disabled {
+    use std::error;
+    use std::fmt;
+
+    /// A compiled pattern, which can't exist without `regex` feature.
+    #[derive(Clone, Debug)]
+    pub enum Regex {}
+
+    impl Regex {
+        pub fn new(_pattern: &str) -> Result<Regex, Error> {
+            Err(Error)
+        }
+
+        pub fn is_match(&self, _s: &str) -> bool {
+            match *self {}
+        }
+
+        pub fn as_str(&self) -> &str {
+            match *self {}
+        }
+    }
+
+    /// Represents that patterns aren't supported.
+    #[derive(Clone, Debug)]
+    pub struct Error;
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Patterns require `regex` feature")
+        }
+    }
+
+    impl error::Error for Error {}
+}
@@ -0,0 +1,1046 @@
+//! JSON Schema validation, a subset of [draft 2020-12](https://json-schema.org/draft/2020-12).
+//!
+//! Supported keywords:
+//! `type`, `enum`, `const`,
+//! `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum`, `multipleOf`,
+//! `minLength`, `maxLength`, `pattern`,
+//! `prefixItems`, `items`, `minItems`, `maxItems`, `uniqueItems`,
+//! `properties`, `patternProperties`, `additionalProperties`, `required`,
+//! `minProperties`, `maxProperties`,
+//! `allOf`, `anyOf`, `oneOf`, `not`,
+//! `$ref` (within the document, by JSON Pointer or `$anchor`) and `$defs`.
+//! Other keywords are ignored.
+//! `pattern` and `patternProperties` require `regex` feature; without it, they fail to compile.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use pointer::Pointer;
+use regex_impl::Regex;
+use {Number, Value};
+
+/// Represents a reason why a schema can't be compiled.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum SchemaError {
+    /// A schema is neither an object nor a boolean.
+    NotSchema { path: Pointer },
+    /// A keyword has a value of wrong shape.
+    InvalidKeyword { path: Pointer, message: String },
+    /// A `pattern` isn't a valid regular expression.
+    InvalidPattern { path: Pointer, message: String },
+    /// A `$ref` doesn't point to a schema in the document.
+    UnresolvedRef { path: Pointer, reference: String },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SchemaError::NotSchema { ref path } => write!(
+                f,
+                "Schema must be an object or boolean at {:?}",
+                path.to_string()
+            ),
+            SchemaError::InvalidKeyword {
+                ref path,
+                ref message,
+            } => write!(f, "{} at {:?}", message, path.to_string()),
+            SchemaError::InvalidPattern {
+                ref path,
+                ref message,
+            } => write!(f, "Invalid pattern at {:?}: {}", path.to_string(), message),
+            SchemaError::UnresolvedRef {
+                ref path,
+                ref reference,
+            } => write!(f, "Can't resolve {:?} at {:?}", reference, path.to_string()),
+        }
+    }
+}
+
+impl ::std::error::Error for SchemaError {}
+
+/// Represents a violation of a schema.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ValidationError {
+    /// Location of the invalid value in the instance.
+    pub instance_path: Pointer,
+    /// Location of the failed keyword in the schema, following `$ref`s.
+    pub schema_path: Pointer,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at {:?} (schema {:?})",
+            self.message,
+            self.instance_path.to_string(),
+            self.schema_path.to_string()
+        )
+    }
+}
+
+impl ::std::error::Error for ValidationError {}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Type {
+    Null,
+    Boolean,
+    Object,
+    Array,
+    Number,
+    String,
+    Integer,
+}
+
+impl Type {
+    fn parse(name: &str) -> Option<Type> {
+        Some(match name {
+            "null" => Type::Null,
+            "boolean" => Type::Boolean,
+            "object" => Type::Object,
+            "array" => Type::Array,
+            "number" => Type::Number,
+            "string" => Type::String,
+            "integer" => Type::Integer,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Type::Null => "null",
+            Type::Boolean => "boolean",
+            Type::Object => "object",
+            Type::Array => "array",
+            Type::Number => "number",
+            Type::String => "string",
+            Type::Integer => "integer",
+        }
+    }
+
+    fn matches(self, value: &Value) -> bool {
+        match (self, value) {
+            (Type::Null, &Value::Null)
+            | (Type::Boolean, &Value::Boolean(_))
+            | (Type::Object, &Value::Object(_))
+            | (Type::Array, &Value::Array(_))
+            | (Type::Number, &Value::Number(_))
+            | (Type::String, &Value::String(_)) => true,
+            (Type::Integer, Value::Number(number)) => is_integer(number),
+            _ => false,
+        }
+    }
+}
+
+fn is_integer(number: &Number) -> bool {
+    number.as_i64().is_some() || number.as_u64().is_some() || number.as_f64().fract() == 0.0
+}
+
+/// Index of a compiled schema in `Schema::nodes`.
+type NodeId = usize;
+
+enum Keyword {
+    Type(Vec<Type>),
+    Enum(Vec<Value>),
+    Const(Value),
+    Minimum(f64),
+    Maximum(f64),
+    ExclusiveMinimum(f64),
+    ExclusiveMaximum(f64),
+    MultipleOf(f64),
+    MinLength(u64),
+    MaxLength(u64),
+    Pattern(Regex),
+    PrefixItems(Vec<NodeId>),
+    /// Applies to items after `prefixItems`.
+    Items(usize, NodeId),
+    MinItems(u64),
+    MaxItems(u64),
+    UniqueItems,
+    Properties(Vec<(String, NodeId)>),
+    PatternProperties(Vec<(Regex, NodeId)>),
+    /// Applies to members which aren't covered by the listed names and patterns.
+    AdditionalProperties(Vec<String>, Vec<Regex>, NodeId),
+    Required(Vec<String>),
+    MinProperties(u64),
+    MaxProperties(u64),
+    AllOf(Vec<NodeId>),
+    AnyOf(Vec<NodeId>),
+    OneOf(Vec<NodeId>),
+    Not(NodeId),
+    Ref(NodeId),
+}
+
+impl Keyword {
+    fn name(&self) -> &'static str {
+        match *self {
+            Keyword::Type(_) => "type",
+            Keyword::Enum(_) => "enum",
+            Keyword::Const(_) => "const",
+            Keyword::Minimum(_) => "minimum",
+            Keyword::Maximum(_) => "maximum",
+            Keyword::ExclusiveMinimum(_) => "exclusiveMinimum",
+            Keyword::ExclusiveMaximum(_) => "exclusiveMaximum",
+            Keyword::MultipleOf(_) => "multipleOf",
+            Keyword::MinLength(_) => "minLength",
+            Keyword::MaxLength(_) => "maxLength",
+            Keyword::Pattern(_) => "pattern",
+            Keyword::PrefixItems(_) => "prefixItems",
+            Keyword::Items(..) => "items",
+            Keyword::MinItems(_) => "minItems",
+            Keyword::MaxItems(_) => "maxItems",
+            Keyword::UniqueItems => "uniqueItems",
+            Keyword::Properties(_) => "properties",
+            Keyword::PatternProperties(_) => "patternProperties",
+            Keyword::AdditionalProperties(..) => "additionalProperties",
+            Keyword::Required(_) => "required",
+            Keyword::MinProperties(_) => "minProperties",
+            Keyword::MaxProperties(_) => "maxProperties",
+            Keyword::AllOf(_) => "allOf",
+            Keyword::AnyOf(_) => "anyOf",
+            Keyword::OneOf(_) => "oneOf",
+            Keyword::Not(_) => "not",
+            Keyword::Ref(_) => "$ref",
+        }
+    }
+}
+
+enum Node {
+    Bool(bool),
+    Keywords(Vec<Keyword>),
+}
+
+/// Represents a compiled JSON Schema.
+pub struct Schema {
+    /// The root is the first node.
+    nodes: Vec<Node>,
+}
+
+impl fmt::Debug for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Schema {{ .. }}")
+    }
+}
+
+// -----------------------------------------------
+// Compile
+// -----------------------------------------------
+
+struct Compiler<'a> {
+    root: &'a Value,
+    /// Nodes being compiled are `None`.
+    nodes: Vec<Option<Node>>,
+    /// Compiled nodes by the location in the document.
+    ids: BTreeMap<Pointer, NodeId>,
+    /// Locations of `$anchor`s.
+    anchors: BTreeMap<String, Pointer>,
+}
+
+fn invalid_keyword(path: &Pointer, message: &str) -> SchemaError {
+    SchemaError::InvalidKeyword {
+        path: path.clone(),
+        message: message.to_string(),
+    }
+}
+
+fn as_number(value: &Value, path: &Pointer) -> Result<f64, SchemaError> {
+    value
+        .as_number()
+        .cloned()
+        .ok_or_else(|| invalid_keyword(path, "Expected a number"))
+}
+
+fn as_count(value: &Value, path: &Pointer) -> Result<u64, SchemaError> {
+    value
+        .as_u64()
+        .ok_or_else(|| invalid_keyword(path, "Expected a non-negative integer"))
+}
+
+fn as_regex(value: &Value, path: &Pointer) -> Result<Regex, SchemaError> {
+    let pattern = value
+        .as_string()
+        .ok_or_else(|| invalid_keyword(path, "Expected a string"))?;
+    Regex::new(pattern).map_err(|err| SchemaError::InvalidPattern {
+        path: path.clone(),
+        message: err.to_string(),
+    })
+}
+
+fn as_strings(value: &Value, path: &Pointer) -> Result<Vec<String>, SchemaError> {
+    let items = value
+        .as_array()
+        .ok_or_else(|| invalid_keyword(path, "Expected an array of strings"))?;
+    items
+        .iter()
+        .map(|item| {
+            item.as_string()
+                .cloned()
+                .ok_or_else(|| invalid_keyword(path, "Expected an array of strings"))
+        })
+        .collect()
+}
+
+/// Decodes `%XX` sequences in a URI fragment.
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = tail
+                .get(..2)
+                .and_then(|hex| ::std::str::from_utf8(hex).ok())?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Finds `$anchor`s in subschemas.
+fn collect_anchors(value: &Value, path: &Pointer, anchors: &mut BTreeMap<String, Pointer>) {
+    match *value {
+        Value::Object(ref object) => {
+            if let Some(Value::String(anchor)) = object.get("$anchor") {
+                anchors
+                    .entry(anchor.to_string())
+                    .or_insert_with(|| path.clone());
+            }
+            for (key, child) in object.iter() {
+                // These contain data rather than schemas.
+                if key == "enum" || key == "const" {
+                    continue;
+                }
+                collect_anchors(child, &path.join(key), anchors);
+            }
+        }
+        Value::Array(ref array) => {
+            for (index, child) in array.iter().enumerate() {
+                collect_anchors(child, &path.join(index), anchors);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl<'a> Compiler<'a> {
+    fn resolve_ref(&self, reference: &str, path: &Pointer) -> Result<Pointer, SchemaError> {
+        let unresolved = || SchemaError::UnresolvedRef {
+            path: path.clone(),
+            reference: reference.to_string(),
+        };
+
+        if !reference.starts_with('#') {
+            return Err(unresolved());
+        }
+        let fragment = percent_decode(&reference[1..]).ok_or_else(unresolved)?;
+
+        let target = if fragment.is_empty() || fragment.starts_with('/') {
+            Pointer::parse(&fragment).map_err(|_| unresolved())?
+        } else {
+            self.anchors
+                .get(&fragment)
+                .cloned()
+                .ok_or_else(unresolved)?
+        };
+
+        if self.root.get_by_pointer(&target).is_err() {
+            return Err(unresolved());
+        }
+        Ok(target)
+    }
+
+    /// Compiles the schema at the location unless it's already compiled.
+    fn compile_at(&mut self, path: &Pointer) -> Result<NodeId, SchemaError> {
+        if let Some(&id) = self.ids.get(path) {
+            return Ok(id);
+        }
+
+        let id = self.nodes.len();
+        self.nodes.push(None);
+        self.ids.insert(path.clone(), id);
+
+        let root = self.root;
+        let schema = root
+            .get_by_pointer(path)
+            .map_err(|_| SchemaError::NotSchema { path: path.clone() })?;
+        let node = self.compile_node(schema, path)?;
+        self.nodes[id] = Some(node);
+        Ok(id)
+    }
+
+    fn compile_list(&mut self, value: &Value, path: &Pointer) -> Result<Vec<NodeId>, SchemaError> {
+        match *value {
+            Value::Array(ref array) if !array.is_empty() => (0..array.len())
+                .map(|index| self.compile_at(&path.join(index)))
+                .collect(),
+            _ => Err(invalid_keyword(
+                path,
+                "Expected a non-empty array of schemas",
+            )),
+        }
+    }
+
+    fn compile_node(&mut self, schema: &Value, path: &Pointer) -> Result<Node, SchemaError> {
+        let object = match *schema {
+            Value::Boolean(value) => return Ok(Node::Bool(value)),
+            Value::Object(ref object) => object,
+            _ => return Err(SchemaError::NotSchema { path: path.clone() }),
+        };
+
+        let mut keywords = Vec::new();
+        for (name, value) in object.iter() {
+            let path = &path.join(name);
+            let keyword = match name.as_str() {
+                "type" => {
+                    let names = match *value {
+                        Value::String(ref name) => vec![name.to_string()],
+                        _ => as_strings(value, path)?,
+                    };
+                    let types = names
+                        .iter()
+                        .map(|name| Type::parse(name))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| invalid_keyword(path, "Unknown type"))?;
+                    Keyword::Type(types)
+                }
+                "enum" => match *value {
+                    Value::Array(ref values) => Keyword::Enum(values.clone()),
+                    _ => return Err(invalid_keyword(path, "Expected an array")),
+                },
+                "const" => Keyword::Const(value.clone()),
+                "minimum" => Keyword::Minimum(as_number(value, path)?),
+                "maximum" => Keyword::Maximum(as_number(value, path)?),
+                "exclusiveMinimum" => Keyword::ExclusiveMinimum(as_number(value, path)?),
+                "exclusiveMaximum" => Keyword::ExclusiveMaximum(as_number(value, path)?),
+                "multipleOf" => match as_number(value, path)? {
+                    divisor if divisor > 0.0 => Keyword::MultipleOf(divisor),
+                    _ => return Err(invalid_keyword(path, "Expected a positive number")),
+                },
+                "minLength" => Keyword::MinLength(as_count(value, path)?),
+                "maxLength" => Keyword::MaxLength(as_count(value, path)?),
+                "pattern" => Keyword::Pattern(as_regex(value, path)?),
+                "prefixItems" => Keyword::PrefixItems(self.compile_list(value, path)?),
+                "items" => {
+                    let start = object
+                        .get("prefixItems")
+                        .and_then(|items| items.as_array())
+                        .map_or(0, |items| items.len());
+                    Keyword::Items(start, self.compile_at(path)?)
+                }
+                "minItems" => Keyword::MinItems(as_count(value, path)?),
+                "maxItems" => Keyword::MaxItems(as_count(value, path)?),
+                "uniqueItems" => match *value {
+                    Value::Boolean(true) => Keyword::UniqueItems,
+                    Value::Boolean(false) => continue,
+                    _ => return Err(invalid_keyword(path, "Expected a boolean")),
+                },
+                "properties" | "patternProperties" => {
+                    let members = value
+                        .as_object()
+                        .ok_or_else(|| invalid_keyword(path, "Expected an object"))?;
+                    if name == "properties" {
+                        let mut properties = Vec::new();
+                        for key in members.keys() {
+                            properties.push((key.to_string(), self.compile_at(&path.join(key))?));
+                        }
+                        Keyword::Properties(properties)
+                    } else {
+                        let mut properties = Vec::new();
+                        for key in members.keys() {
+                            let regex = as_regex(&Value::String(key.to_string()), path)?;
+                            properties.push((regex, self.compile_at(&path.join(key))?));
+                        }
+                        Keyword::PatternProperties(properties)
+                    }
+                }
+                "additionalProperties" => {
+                    let names = object
+                        .get("properties")
+                        .and_then(|properties| properties.as_object())
+                        .map_or_else(Vec::new, |properties| properties.keys().cloned().collect());
+                    let mut patterns = Vec::new();
+                    if let Some(properties) = object
+                        .get("patternProperties")
+                        .and_then(|properties| properties.as_object())
+                    {
+                        for key in properties.keys() {
+                            patterns.push(as_regex(&Value::String(key.to_string()), path)?);
+                        }
+                    }
+                    Keyword::AdditionalProperties(names, patterns, self.compile_at(path)?)
+                }
+                "required" => Keyword::Required(as_strings(value, path)?),
+                "minProperties" => Keyword::MinProperties(as_count(value, path)?),
+                "maxProperties" => Keyword::MaxProperties(as_count(value, path)?),
+                "allOf" => Keyword::AllOf(self.compile_list(value, path)?),
+                "anyOf" => Keyword::AnyOf(self.compile_list(value, path)?),
+                "oneOf" => Keyword::OneOf(self.compile_list(value, path)?),
+                "not" => Keyword::Not(self.compile_at(path)?),
+                "$ref" => {
+                    let reference = value
+                        .as_string()
+                        .ok_or_else(|| invalid_keyword(path, "Expected a string"))?;
+                    let target = self.resolve_ref(reference, path)?;
+                    Keyword::Ref(self.compile_at(&target)?)
+                }
+                "$defs" => {
+                    // Compile definitions eagerly to report errors in unused ones too.
+                    let defs = value
+                        .as_object()
+                        .ok_or_else(|| invalid_keyword(path, "Expected an object"))?;
+                    for key in defs.keys() {
+                        self.compile_at(&path.join(key))?;
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            keywords.push(keyword);
+        }
+        Ok(Node::Keywords(keywords))
+    }
+}
+
+impl Schema {
+    /// Compiles a schema document.
+    pub fn compile(schema: &Value) -> Result<Schema, SchemaError> {
+        let mut anchors = BTreeMap::new();
+        collect_anchors(schema, &Pointer::root(), &mut anchors);
+
+        let mut compiler = Compiler {
+            root: schema,
+            nodes: Vec::new(),
+            ids: BTreeMap::new(),
+            anchors,
+        };
+        compiler.compile_at(&Pointer::root())?;
+
+        let nodes = compiler
+            .nodes
+            .into_iter()
+            .map(|node| node.expect("All nodes are compiled"))
+            .collect();
+        Ok(Schema { nodes })
+    }
+
+    /// Validates an instance and returns all violations.
+    pub fn validate(&self, instance: &Value) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        self.validate_node(
+            0,
+            instance,
+            &Pointer::root(),
+            &Pointer::root(),
+            &[],
+            &mut errors,
+        );
+        errors
+    }
+
+    /// Determines if an instance is valid.
+    pub fn is_valid(&self, instance: &Value) -> bool {
+        self.validate(instance).is_empty()
+    }
+
+    fn is_valid_at(&self, id: NodeId, instance: &Value, applied: &[NodeId]) -> bool {
+        let mut errors = Vec::new();
+        let root = Pointer::root();
+        self.validate_node(id, instance, &root, &root, applied, &mut errors);
+        errors.is_empty()
+    }
+
+    /// `applied` is the nodes already applied to the same instance,
+    /// to detect `$ref` loops that never descend into the instance.
+    fn validate_node(
+        &self,
+        id: NodeId,
+        instance: &Value,
+        instance_path: &Pointer,
+        schema_path: &Pointer,
+        applied: &[NodeId],
+        errors: &mut Vec<ValidationError>,
+    ) {
+        let error = |errors: &mut Vec<ValidationError>, schema_path: Pointer, message: String| {
+            errors.push(ValidationError {
+                instance_path: instance_path.clone(),
+                schema_path,
+                message,
+            });
+        };
+
+        let keywords = match self.nodes[id] {
+            Node::Bool(true) => return,
+            Node::Bool(false) => {
+                error(
+                    errors,
+                    schema_path.clone(),
+                    "No value is allowed".to_string(),
+                );
+                return;
+            }
+            Node::Keywords(ref keywords) => keywords,
+        };
+
+        if applied.contains(&id) {
+            error(
+                errors,
+                schema_path.clone(),
+                "Schema refers to itself infinitely".to_string(),
+            );
+            return;
+        }
+        let mut applied = applied.to_vec();
+        applied.push(id);
+        let applied = &applied;
+
+        for keyword in keywords {
+            let keyword_path = schema_path.join(keyword.name());
+
+            match (keyword, instance) {
+                (Keyword::PrefixItems(ids), Value::Array(array)) => {
+                    for (index, (&id, item)) in ids.iter().zip(array.iter()).enumerate() {
+                        self.validate_node(
+                            id,
+                            item,
+                            &instance_path.join(index),
+                            &keyword_path.join(index),
+                            &[],
+                            errors,
+                        );
+                    }
+                }
+                (&Keyword::Items(start, id), Value::Array(array)) => {
+                    for (index, item) in array.iter().enumerate().skip(start) {
+                        self.validate_node(
+                            id,
+                            item,
+                            &instance_path.join(index),
+                            &keyword_path,
+                            &[],
+                            errors,
+                        );
+                    }
+                }
+                (Keyword::Properties(properties), Value::Object(object)) => {
+                    for &(ref key, id) in properties {
+                        if let Some(value) = object.get(key) {
+                            self.validate_node(
+                                id,
+                                value,
+                                &instance_path.join(key),
+                                &keyword_path.join(key),
+                                &[],
+                                errors,
+                            );
+                        }
+                    }
+                }
+                (Keyword::PatternProperties(properties), Value::Object(object)) => {
+                    for &(ref regex, id) in properties {
+                        for (key, value) in object.iter().filter(|&(key, _)| regex.is_match(key)) {
+                            self.validate_node(
+                                id,
+                                value,
+                                &instance_path.join(key),
+                                &keyword_path.join(regex.as_str()),
+                                &[],
+                                errors,
+                            );
+                        }
+                    }
+                }
+                (Keyword::AdditionalProperties(names, patterns, id), Value::Object(object)) => {
+                    for (key, value) in object.iter() {
+                        if names.contains(key) || patterns.iter().any(|regex| regex.is_match(key)) {
+                            continue;
+                        }
+                        self.validate_node(
+                            *id,
+                            value,
+                            &instance_path.join(key),
+                            &keyword_path,
+                            &[],
+                            errors,
+                        );
+                    }
+                }
+                (Keyword::Required(keys), Value::Object(object)) => {
+                    for key in keys.iter().filter(|key| !object.contains_key(*key)) {
+                        error(
+                            errors,
+                            keyword_path.clone(),
+                            format!("Missing property {:?}", key),
+                        );
+                    }
+                }
+                (Keyword::AllOf(ids), _) => {
+                    for (index, &id) in ids.iter().enumerate() {
+                        self.validate_node(
+                            id,
+                            instance,
+                            instance_path,
+                            &keyword_path.join(index),
+                            applied,
+                            errors,
+                        );
+                    }
+                }
+                (&Keyword::Ref(id), _) => {
+                    self.validate_node(id, instance, instance_path, &keyword_path, applied, errors);
+                }
+                _ => {
+                    if let Some(message) = self.check(keyword, instance, applied) {
+                        error(errors, keyword_path, message);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks a keyword which reports at most one error by itself.
+    /// Returns the error message if the instance is invalid.
+    fn check(&self, keyword: &Keyword, instance: &Value, applied: &[NodeId]) -> Option<String> {
+        let valid = match (keyword, instance) {
+            (Keyword::Type(types), _) => types.iter().any(|ty| ty.matches(instance)),
+            (Keyword::Enum(values), _) => values.contains(instance),
+            (Keyword::Const(value), _) => instance == value,
+            (&Keyword::Minimum(limit), Value::Number(n)) => n.as_f64() >= limit,
+            (&Keyword::Maximum(limit), Value::Number(n)) => n.as_f64() <= limit,
+            (&Keyword::ExclusiveMinimum(limit), Value::Number(n)) => n.as_f64() > limit,
+            (&Keyword::ExclusiveMaximum(limit), Value::Number(n)) => n.as_f64() < limit,
+            (&Keyword::MultipleOf(divisor), Value::Number(n)) => {
+                is_multiple_of(n.as_f64(), divisor)
+            }
+            (&Keyword::MinLength(limit), Value::String(s)) => s.chars().count() as u64 >= limit,
+            (&Keyword::MaxLength(limit), Value::String(s)) => s.chars().count() as u64 <= limit,
+            (Keyword::Pattern(regex), Value::String(s)) => regex.is_match(s),
+            (&Keyword::MinItems(limit), Value::Array(array)) => array.len() as u64 >= limit,
+            (&Keyword::MaxItems(limit), Value::Array(array)) => array.len() as u64 <= limit,
+            (Keyword::UniqueItems, Value::Array(array)) => {
+                (0..array.len()).all(|i| !array[i + 1..].contains(&array[i]))
+            }
+            (&Keyword::MinProperties(limit), Value::Object(object)) => object.len() as u64 >= limit,
+            (&Keyword::MaxProperties(limit), Value::Object(object)) => object.len() as u64 <= limit,
+            (Keyword::AnyOf(ids), _) => ids
+                .iter()
+                .any(|&id| self.is_valid_at(id, instance, applied)),
+            (Keyword::OneOf(ids), _) => {
+                let count = ids
+                    .iter()
+                    .filter(|&&id| self.is_valid_at(id, instance, applied))
+                    .count();
+                if count != 1 {
+                    return Some(format!(
+                        "Value must match exactly one schema but matches {}",
+                        count
+                    ));
+                }
+                true
+            }
+            (&Keyword::Not(id), _) => !self.is_valid_at(id, instance, applied),
+            // Keywords for other types don't apply.
+            _ => true,
+        };
+        if valid {
+            return None;
+        }
+
+        Some(match *keyword {
+            Keyword::Type(ref types) => {
+                let names = types.iter().map(|ty| ty.name()).collect::<Vec<_>>();
+                format!("Expected {}", names.join(" or "))
+            }
+            Keyword::Enum(_) => "Value isn't one of the allowed values".to_string(),
+            Keyword::Const(ref value) => format!("Expected {}", value.serialize()),
+            Keyword::Minimum(limit) => format!("Must be >= {}", limit),
+            Keyword::Maximum(limit) => format!("Must be <= {}", limit),
+            Keyword::ExclusiveMinimum(limit) => format!("Must be > {}", limit),
+            Keyword::ExclusiveMaximum(limit) => format!("Must be < {}", limit),
+            Keyword::MultipleOf(divisor) => format!("Must be a multiple of {}", divisor),
+            Keyword::MinLength(limit) => format!("Must be at least {} characters", limit),
+            Keyword::MaxLength(limit) => format!("Must be at most {} characters", limit),
+            Keyword::Pattern(ref regex) => format!("Must match /{}/", regex.as_str()),
+            Keyword::MinItems(limit) => format!("Must have at least {} items", limit),
+            Keyword::MaxItems(limit) => format!("Must have at most {} items", limit),
+            Keyword::UniqueItems => "Items must be unique".to_string(),
+            Keyword::MinProperties(limit) => format!("Must have at least {} properties", limit),
+            Keyword::MaxProperties(limit) => format!("Must have at most {} properties", limit),
+            Keyword::AnyOf(_) => "Value doesn't match any of the schemas".to_string(),
+            Keyword::Not(_) => "Value must not match the schema".to_string(),
+            _ => unreachable!(),
+        })
+    }
+}
+
+/// Checks if the number is a multiple of the divisor,
+/// allowing rounding errors of decimals such as `0.3 / 0.1 = 2.9999999999999996`.
+fn is_multiple_of(n: f64, divisor: f64) -> bool {
+    let quotient = n / divisor;
+    if !quotient.is_finite() {
+        return true;
+    }
+    (quotient - quotient.round()).abs() <= quotient.abs() * f64::EPSILON * 4.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_string;
+
+    fn compile(schema: &str) -> Schema {
+        Schema::compile(&parse_string(schema).unwrap()).unwrap()
+    }
+
+    /// Validates the instance and returns pairs of instance path and schema path of errors, sorted.
+    fn errors(schema: &Schema, instance: &str) -> Vec<(String, String)> {
+        let mut errors = schema
+            .validate(&parse_string(instance).unwrap())
+            .iter()
+            .map(|err| (err.instance_path.to_string(), err.schema_path.to_string()))
+            .collect::<Vec<_>>();
+        errors.sort();
+        errors
+    }
+
+    fn pair(instance_path: &str, schema_path: &str) -> (String, String) {
+        (instance_path.to_string(), schema_path.to_string())
+    }
+
+    #[test]
+    fn test_type() {
+        let schema = compile(r#"{ "type": ["integer", "null"] }"#);
+        assert!(schema.is_valid(&parse_string("1").unwrap()));
+        assert!(schema.is_valid(&parse_string("2.0").unwrap()));
+        assert!(schema.is_valid(&parse_string("null").unwrap()));
+        assert_eq!(errors(&schema, "1.5"), vec![pair("", "/type")]);
+        assert_eq!(
+            schema.validate(&parse_string(r#""1""#).unwrap())[0].message,
+            "Expected integer or null"
+        );
+
+        assert!(compile("true").is_valid(&Value::Null));
+        assert_eq!(errors(&compile("false"), "null"), vec![pair("", "")]);
+    }
+
+    #[test]
+    fn test_enum_const() {
+        let schema = compile(r#"{ "enum": [1, "a", { "b": [null] }] }"#);
+        assert!(schema.is_valid(&parse_string("1.0").unwrap()));
+        assert!(schema.is_valid(&parse_string(r#"{ "b": [null] }"#).unwrap()));
+        assert_eq!(errors(&schema, r#""b""#), vec![pair("", "/enum")]);
+
+        let schema = compile(r#"{ "const": [1, 2] }"#);
+        assert!(schema.is_valid(&parse_string("[1, 2]").unwrap()));
+        assert_eq!(errors(&schema, "[2, 1]"), vec![pair("", "/const")]);
+    }
+
+    #[test]
+    fn test_numbers_and_strings() {
+        let schema = compile(
+            r#"{
+                "minimum": 0, "exclusiveMaximum": 10, "multipleOf": 0.5,
+                "minLength": 2, "maxLength": 3
+            }"#,
+        );
+        assert!(schema.is_valid(&parse_string("9.5").unwrap()));
+        assert!(schema.is_valid(&parse_string(r#""あい""#).unwrap()));
+        assert_eq!(errors(&schema, "-1"), vec![pair("", "/minimum")]);
+        assert_eq!(errors(&schema, "10"), vec![pair("", "/exclusiveMaximum")]);
+        assert_eq!(errors(&schema, "0.25"), vec![pair("", "/multipleOf")]);
+        assert_eq!(errors(&schema, r#""abcd""#), vec![pair("", "/maxLength")]);
+        assert_eq!(errors(&schema, r#""A""#), vec![pair("", "/minLength")]);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_pattern() {
+        let schema = compile(r#"{ "pattern": "^\\w+$" }"#);
+        assert!(schema.is_valid(&parse_string(r#""あい""#).unwrap()));
+        assert_eq!(errors(&schema, r#""a-""#), vec![pair("", "/pattern")]);
+    }
+
+    #[cfg(not(feature = "regex"))]
+    #[test]
+    fn test_pattern_requires_regex() {
+        let err = Schema::compile(&parse_string(r#"{ "pattern": "^\\w+$" }"#).unwrap())
+            .expect_err("Expected an error.");
+        assert_eq!(
+            err.to_string(),
+            r#"Invalid pattern at "/pattern": Patterns require `regex` feature"#
+        );
+    }
+
+    #[test]
+    fn test_multiple_of_decimals() {
+        let schema = compile(r#"{ "multipleOf": 0.1 }"#);
+        for instance in &["0", "0.3", "0.7", "1.1", "-4.2", "123456.7"] {
+            assert!(
+                schema.is_valid(&parse_string(instance).unwrap()),
+                "{}",
+                instance
+            );
+        }
+        for instance in &["0.25", "0.31", "1e-18"] {
+            assert!(
+                !schema.is_valid(&parse_string(instance).unwrap()),
+                "{}",
+                instance
+            );
+        }
+
+        let schema = compile(r#"{ "multipleOf": 0.01 }"#);
+        assert!(schema.is_valid(&parse_string("4.35").unwrap()));
+        assert!(!schema.is_valid(&parse_string("4.355").unwrap()));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_objects() {
+        let schema = compile(
+            r#"{
+                "type": "object",
+                "properties": { "name": { "type": "string" }, "age": { "minimum": 0 } },
+                "patternProperties": { "^x-": { "type": "string" } },
+                "additionalProperties": false,
+                "required": ["name", "age"]
+            }"#,
+        );
+        assert!(schema.is_valid(&parse_string(r#"{ "name": "a", "age": 1, "x-y": "z" }"#).unwrap()));
+        assert_eq!(
+            errors(&schema, r#"{ "name": 1, "age": -1, "x-y": 1, "z": 0 }"#),
+            vec![
+                pair("/age", "/properties/age/minimum"),
+                pair("/name", "/properties/name/type"),
+                pair("/x-y", "/patternProperties/^x-/type"),
+                pair("/z", "/additionalProperties"),
+            ]
+        );
+        assert_eq!(
+            errors(&schema, r#"{ "age": 1 }"#),
+            vec![pair("", "/required")]
+        );
+    }
+
+    #[test]
+    fn test_arrays() {
+        let schema = compile(
+            r#"{
+                "prefixItems": [{ "type": "string" }],
+                "items": { "type": "number" },
+                "minItems": 1, "maxItems": 3, "uniqueItems": true
+            }"#,
+        );
+        assert!(schema.is_valid(&parse_string(r#"["a", 1, 2]"#).unwrap()));
+        assert_eq!(errors(&schema, "[]"), vec![pair("", "/minItems")]);
+        assert_eq!(
+            errors(&schema, r#"[1, "a", 2, 2]"#),
+            vec![
+                pair("", "/maxItems"),
+                pair("", "/uniqueItems"),
+                pair("/0", "/prefixItems/0/type"),
+                pair("/1", "/items/type"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_combinators() {
+        let schema = compile(
+            r#"{
+                "allOf": [{ "type": "number" }, { "minimum": 0 }],
+                "anyOf": [{ "maximum": 1 }, { "minimum": 10 }],
+                "oneOf": [{ "multipleOf": 2 }, { "multipleOf": 3 }],
+                "not": { "const": 12 }
+            }"#,
+        );
+        assert!(!schema.is_valid(&parse_string("0.5").unwrap()));
+        assert!(schema.is_valid(&parse_string("10").unwrap()));
+        assert_eq!(errors(&schema, "-2"), vec![pair("", "/allOf/1/minimum")]);
+        assert_eq!(errors(&schema, "4"), vec![pair("", "/anyOf")]);
+        assert_eq!(
+            errors(&schema, "12"),
+            vec![pair("", "/not"), pair("", "/oneOf")]
+        );
+    }
+
+    #[test]
+    fn test_ref() {
+        let schema = compile(
+            r##"{
+                "$defs": {
+                    "node": {
+                        "type": "object",
+                        "properties": {
+                            "value": { "$ref": "#/$defs/value" },
+                            "children": { "items": { "$ref": "#/$defs/node" } }
+                        }
+                    },
+                    "value": { "$anchor": "value", "type": "integer" }
+                },
+                "$ref": "#/$defs/node",
+                "properties": { "extra": { "$ref": "#value" } }
+            }"##,
+        );
+        assert!(schema.is_valid(
+            &parse_string(r#"{ "value": 1, "children": [{ "value": 2, "children": [] }] }"#)
+                .unwrap()
+        ));
+        assert_eq!(
+            errors(
+                &schema,
+                r#"{ "extra": "x", "children": [{ "children": [{ "value": true }] }] }"#
+            ),
+            vec![
+                pair(
+                    "/children/0/children/0/value",
+                    "/$ref/properties/children/items/$ref/properties/children/items/$ref/properties/value/$ref/type"
+                ),
+                pair("/extra", "/properties/extra/$ref/type"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ref_loop() {
+        let schema =
+            compile(r##"{ "$defs": { "a": { "$ref": "#/$defs/a" } }, "$ref": "#/$defs/a" }"##);
+        assert_eq!(errors(&schema, "1"), vec![pair("", "/$ref/$ref")]);
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        fn compile_err(schema: &str) -> SchemaError {
+            Schema::compile(&parse_string(schema).unwrap()).expect_err("Expected an error.")
+        }
+
+        assert_eq!(
+            compile_err(r#"{ "properties": { "a": 1 } }"#),
+            SchemaError::NotSchema {
+                path: Pointer::parse("/properties/a").unwrap()
+            }
+        );
+        assert_eq!(
+            compile_err(r#"{ "type": "list" }"#).to_string(),
+            r#"Unknown type at "/type""#
+        );
+        match compile_err(r#"{ "pattern": "(" }"#) {
+            SchemaError::InvalidPattern { path, .. } => assert_eq!(path.to_string(), "/pattern"),
+            err => panic!("Unexpected error: {:?}", err),
+        }
+        assert_eq!(
+            compile_err(r##"{ "$ref": "#/$defs/missing" }"##),
+            SchemaError::UnresolvedRef {
+                path: Pointer::parse("/$ref").unwrap(),
+                reference: "#/$defs/missing".to_string(),
+            }
+        );
+        assert_eq!(
+            compile_err(r#"{ "$ref": "other.json" }"#).to_string(),
+            r#"Can't resolve "other.json" at "/$ref""#
+        );
+    }
+}
@@ -0,0 +1,1139 @@
+//! Interoperation with [serde](https://serde.rs), enabled by `serde` feature.
+//!
+//! Conversions go through `Value`, so that `from_str` and `to_string`
+//! use the parser and serializer of this crate.
+//!
+//! Numbers are always converted by the numeric value, since serde has no way to pass the text;
+//! the source text kept by `ParseOptions::keep_number_text` is dropped,
+//! e.g. `1.50` becomes `1.5`. Use `Value::serialize` to keep it.
+
+use std::error;
+use std::fmt;
+
+use serde::de::{self, DeserializeOwned, IntoDeserializer};
+use serde::ser::{self, Serialize};
+
+use pointer::Pointer;
+use {parse_string, Exact, Number, Object, Value};
+
+/// Represents an error occurred while converting between values and Rust types.
+#[derive(Debug)]
+pub enum SerdeError {
+    /// The input isn't valid JSON.
+    Parse(::Error),
+    /// The value doesn't match the type.
+    /// `path` points to the value where the error occurred.
+    Data {
+        message: String,
+        path: Option<Pointer>,
+    },
+}
+
+impl SerdeError {
+    fn message<T: fmt::Display>(message: T) -> SerdeError {
+        SerdeError::Data {
+            message: message.to_string(),
+            path: None,
+        }
+    }
+
+    /// Gets the location of the value which caused the error.
+    pub fn path(&self) -> Option<&Pointer> {
+        match *self {
+            SerdeError::Data { ref path, .. } => path.as_ref(),
+            SerdeError::Parse(_) => None,
+        }
+    }
+
+    /// Sets the path unless a deeper path is already set.
+    fn at(self, at: &Pointer) -> SerdeError {
+        match self {
+            SerdeError::Data {
+                message,
+                path: None,
+            } => SerdeError::Data {
+                message,
+                path: Some(at.clone()),
+            },
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SerdeError::Parse(ref err) => write!(f, "{}", err),
+            SerdeError::Data {
+                ref message,
+                path: Some(ref path),
+            } => write!(f, "{} at {:?}", message, path.to_string()),
+            SerdeError::Data {
+                ref message,
+                path: None,
+            } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl error::Error for SerdeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            SerdeError::Parse(ref err) => Some(err),
+            SerdeError::Data { .. } => None,
+        }
+    }
+}
+
+impl ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> SerdeError {
+        SerdeError::message(message)
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> SerdeError {
+        SerdeError::message(message)
+    }
+}
+
+// -----------------------------------------------
+// Value <-> serde
+// -----------------------------------------------
+
+/// Serializes the exact integer or the `f64` value. The kept source text isn't used.
+impl Serialize for Number {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.exact {
+            Exact::I64(n) => serializer.serialize_i64(n),
+            Exact::U64(n) => serializer.serialize_u64(n),
+            Exact::None => serializer.serialize_f64(self.value),
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Value::Null => serializer.serialize_unit(),
+            Value::Boolean(value) => serializer.serialize_bool(value),
+            Value::Number(ref number) => number.serialize(serializer),
+            Value::String(ref value) => serializer.serialize_str(value),
+            Value::Array(ref array) => array.serialize(serializer),
+            Value::Object(ref object) => {
+                use serde::ser::SerializeMap;
+
+                let mut map = serializer.serialize_map(Some(object.len()))?;
+                for (key, value) in object.iter() {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> de::Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "any JSON value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        de::Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Value, E> {
+        Ok(Value::Boolean(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Value, E> {
+        Ok(Value::Number(Number::from(value)))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Value, E> {
+        Ok(Value::Number(Number::from(value)))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Value, E> {
+        Value::try_from_number(value).map_err(E::custom)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Value, E> {
+        Ok(Value::String(value.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Value, E> {
+        Ok(Value::String(value))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut array = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            array.push(item);
+        }
+        Ok(Value::Array(array))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut object = Object::new();
+        while let Some((key, value)) = map.next_entry()? {
+            object.insert(key, value);
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Value {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+// -----------------------------------------------
+// Serializer
+// -----------------------------------------------
+
+/// Serializes Rust values into `Value`.
+struct ValueSerializer;
+
+fn number_value(value: f64) -> Result<Value, SerdeError> {
+    Value::try_from_number(value).map_err(SerdeError::message)
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeVariant<SerializeArray>;
+    type SerializeMap = SerializeObject;
+    type SerializeStruct = SerializeObject;
+    type SerializeStructVariant = SerializeVariant<SerializeObject>;
+
+    fn serialize_bool(self, value: bool) -> Result<Value, SerdeError> {
+        Ok(Value::Boolean(value))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<Value, SerdeError> {
+        self.serialize_i64(value as i64)
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<Value, SerdeError> {
+        self.serialize_i64(value as i64)
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<Value, SerdeError> {
+        self.serialize_i64(value as i64)
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<Value, SerdeError> {
+        Ok(Value::Number(Number::from(value)))
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<Value, SerdeError> {
+        self.serialize_u64(value as u64)
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<Value, SerdeError> {
+        self.serialize_u64(value as u64)
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<Value, SerdeError> {
+        self.serialize_u64(value as u64)
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<Value, SerdeError> {
+        Ok(Value::Number(Number::from(value)))
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<Value, SerdeError> {
+        number_value(value as f64)
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<Value, SerdeError> {
+        number_value(value)
+    }
+
+    fn serialize_char(self, value: char) -> Result<Value, SerdeError> {
+        Ok(Value::String(value.to_string()))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<Value, SerdeError> {
+        Ok(Value::String(value.to_string()))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<Value, SerdeError> {
+        Ok(Value::Array(
+            value
+                .iter()
+                .map(|&b| Value::Number(Number::from(b as u64)))
+                .collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Value, SerdeError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Value, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, SerdeError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, SerdeError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, SerdeError> {
+        Ok(Value::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, SerdeError> {
+        let mut object = Object::new();
+        object.insert(variant.to_string(), value.serialize(self)?);
+        Ok(Value::Object(object))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, SerdeError> {
+        Ok(SerializeArray {
+            array: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeArray>, SerdeError> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeObject, SerdeError> {
+        Ok(SerializeObject {
+            object: Object::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeObject, SerdeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeObject>, SerdeError> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SerializeArray {
+    array: Vec<Value>,
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.array.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(Value::Array(self.array))
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct SerializeObject {
+    object: Object,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for SerializeObject {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.key = Some(key.serialize(MapKeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| SerdeError::message("serialize_value called before serialize_key"))?;
+        self.object.insert(key, value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(Value::Object(self.object))
+    }
+}
+
+impl ser::SerializeStruct for SerializeObject {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.object
+            .insert(key.to_string(), value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+/// Wraps the content of an enum variant as `{ "variant": content }`.
+struct SerializeVariant<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl<S> SerializeVariant<S> {
+    fn wrap(variant: &'static str, content: Value) -> Value {
+        let mut object = Object::new();
+        object.insert(variant.to_string(), content);
+        Value::Object(object)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeArray> {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        let content = ser::SerializeSeq::end(self.inner)?;
+        Ok(Self::wrap(self.variant, content))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeObject> {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        let content = ser::SerializeMap::end(self.inner)?;
+        Ok(Self::wrap(self.variant, content))
+    }
+}
+
+/// Serializes keys of maps into strings. Keys must be strings, chars or integers.
+struct MapKeySerializer;
+
+fn key_must_be_string() -> SerdeError {
+    SerdeError::message("Key must be a string")
+}
+
+impl ser::Serializer for MapKeySerializer {
+    type Ok = String;
+    type Error = SerdeError;
+    type SerializeSeq = ser::Impossible<String, SerdeError>;
+    type SerializeTuple = ser::Impossible<String, SerdeError>;
+    type SerializeTupleStruct = ser::Impossible<String, SerdeError>;
+    type SerializeTupleVariant = ser::Impossible<String, SerdeError>;
+    type SerializeMap = ser::Impossible<String, SerdeError>;
+    type SerializeStruct = ser::Impossible<String, SerdeError>;
+    type SerializeStructVariant = ser::Impossible<String, SerdeError>;
+
+    fn serialize_bool(self, _value: bool) -> Result<String, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<String, SerdeError> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<String, SerdeError> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<String, SerdeError> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<String, SerdeError> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<String, SerdeError> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<String, SerdeError> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<String, SerdeError> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<String, SerdeError> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_f32(self, _value: f32) -> Result<String, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_f64(self, _value: f64) -> Result<String, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_char(self, value: char) -> Result<String, SerdeError> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_str(self, value: &str) -> Result<String, SerdeError> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<String, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_none(self) -> Result<String, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<String, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_unit(self) -> Result<String, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<String, SerdeError> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerdeError> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        Err(key_must_be_string())
+    }
+}
+
+// -----------------------------------------------
+// Deserializer
+// -----------------------------------------------
+
+/// Deserializes Rust values from a `Value`, keeping track of the location in the document.
+struct ValueDeserializer {
+    value: Value,
+    path: Pointer,
+}
+
+impl ValueDeserializer {
+    fn new(value: Value, path: Pointer) -> ValueDeserializer {
+        ValueDeserializer { value, path }
+    }
+}
+
+macro_rules! forward_with_path {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+                self.deserialize_any(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let path = self.path;
+        let result = match self.value {
+            Value::Null => visitor.visit_unit(),
+            Value::Boolean(value) => visitor.visit_bool(value),
+            Value::Number(number) => match number.exact {
+                Exact::I64(n) => visitor.visit_i64(n),
+                Exact::U64(n) => visitor.visit_u64(n),
+                Exact::None => visitor.visit_f64(number.value),
+            },
+            Value::String(value) => visitor.visit_string(value),
+            Value::Array(array) => {
+                let len = array.len();
+                let mut seq = SeqDeserializer {
+                    iter: array.into_iter().enumerate(),
+                    path: path.clone(),
+                };
+                let value = visitor.visit_seq(&mut seq);
+                match value {
+                    Ok(_) if seq.iter.len() != 0 => {
+                        Err(de::Error::invalid_length(len, &"fewer elements in array"))
+                    }
+                    _ => value,
+                }
+            }
+            Value::Object(object) => visitor.visit_map(MapDeserializer {
+                iter: object.into_iter(),
+                value: None,
+                path: path.clone(),
+            }),
+        };
+        result.map_err(|err| err.at(&path))
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let path = self.path;
+        let (variant, content) = match self.value {
+            Value::String(variant) => (variant, None),
+            Value::Object(object) => {
+                if object.len() != 1 {
+                    return Err(
+                        SerdeError::message("Enum must be an object with a single key").at(&path),
+                    );
+                }
+                let (variant, content) = object.into_iter().next().unwrap();
+                (variant, Some(content))
+            }
+            _ => {
+                return Err(SerdeError::message("Enum must be a string or object").at(&path));
+            }
+        };
+
+        let content_path = path.join(&variant);
+        visitor
+            .visit_enum(EnumDeserializer {
+                variant,
+                content,
+                path: content_path,
+            })
+            .map_err(|err| err.at(&path))
+    }
+
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_unit()
+    }
+
+    forward_with_path! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf deserialize_unit deserialize_seq deserialize_map
+        deserialize_identifier
+    }
+
+    fn deserialize_unit_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple<V: de::Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_any(visitor)
+    }
+}
+
+struct SeqDeserializer {
+    iter: ::std::iter::Enumerate<::std::vec::IntoIter<Value>>,
+    path: Pointer,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqDeserializer {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        match self.iter.next() {
+            Some((index, value)) => seed
+                .deserialize(ValueDeserializer::new(value, self.path.join(index)))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapDeserializer {
+    iter: <Object as IntoIterator>::IntoIter,
+    value: Option<(String, Value)>,
+    path: Pointer,
+}
+
+impl<'de> de::MapAccess<'de> for MapDeserializer {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SerdeError> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                let path = self.path.join(&key);
+                let result = seed.deserialize(key.clone().into_deserializer());
+                self.value = Some((key, value));
+                result.map(Some).map_err(|err: SerdeError| err.at(&path))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.value.take() {
+            Some((key, value)) => {
+                seed.deserialize(ValueDeserializer::new(value, self.path.join(key)))
+            }
+            None => Err(SerdeError::message(
+                "next_value_seed called before next_key_seed",
+            )),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    content: Option<Value>,
+    path: Pointer,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = SerdeError;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantDeserializer), SerdeError> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        let rest = VariantDeserializer {
+            content: self.content,
+            path: self.path,
+        };
+        Ok((variant, rest))
+    }
+}
+
+struct VariantDeserializer {
+    content: Option<Value>,
+    path: Pointer,
+}
+
+impl VariantDeserializer {
+    fn into_content(self) -> Result<ValueDeserializer, SerdeError> {
+        match self.content {
+            Some(content) => Ok(ValueDeserializer::new(content, self.path)),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"variant with content",
+            )),
+        }
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        match self.content {
+            None | Some(Value::Null) => Ok(()),
+            Some(_) => Err(de::Error::invalid_type(
+                de::Unexpected::NewtypeVariant,
+                &"unit variant",
+            )),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        seed.deserialize(self.into_content()?)
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_seq(self.into_content()?, visitor)
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_map(self.into_content()?, visitor)
+    }
+}
+
+// -----------------------------------------------
+// Entry points
+// -----------------------------------------------
+
+/// Converts a Rust value into a `Value`.
+pub fn to_value<T: ?Sized + Serialize>(value: &T) -> Result<Value, SerdeError> {
+    value.serialize(ValueSerializer)
+}
+
+/// Converts a `Value` into a Rust value.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, SerdeError> {
+    T::deserialize(ValueDeserializer::new(value, Pointer::root()))
+}
+
+/// Parses a JSON string into a Rust value.
+pub fn from_str<T: DeserializeOwned>(s: &str) -> Result<T, SerdeError> {
+    from_value(parse_string(s).map_err(SerdeError::Parse)?)
+}
+
+/// Serializes a Rust value into a compact JSON string.
+pub fn to_string<T: ?Sized + Serialize>(value: &T) -> Result<String, SerdeError> {
+    Ok(to_value(value)?.serialize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Rect { width: u32, height: u32 },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Item {
+        id: u64,
+        name: String,
+        tags: Vec<String>,
+        shape: Shape,
+        note: Option<String>,
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let items = vec![
+            Item {
+                id: 18_446_744_073_709_551_615,
+                name: "あ\"b".to_string(),
+                tags: vec!["x".to_string()],
+                shape: Shape::Rect {
+                    width: 2,
+                    height: 3,
+                },
+                note: None,
+            },
+            Item {
+                id: 1,
+                name: "c".to_string(),
+                tags: vec![],
+                shape: Shape::Circle(1.5),
+                note: Some("n".to_string()),
+            },
+        ];
+
+        let json = to_string(&items).unwrap();
+        let expected = if cfg!(feature = "preserve_order") {
+            r#"[{"id":18446744073709551615,"name":"あ\"b","tags":["x"],"shape":{"Rect":{"width":2,"height":3}},"note":null},{"id":1,"name":"c","tags":[],"shape":{"Circle":1.5},"note":"n"}]"#
+        } else {
+            r#"[{"id":18446744073709551615,"name":"あ\"b","note":null,"shape":{"Rect":{"height":3,"width":2}},"tags":["x"]},{"id":1,"name":"c","note":"n","shape":{"Circle":1.5},"tags":[]}]"#
+        };
+        assert_eq!(json, expected);
+        assert_eq!(from_str::<Vec<Item>>(&json).unwrap(), items);
+    }
+
+    #[test]
+    fn test_value_roundtrip() {
+        let value = parse_string(r#"{ "a": [1, -2, 3.5, true, null, "s"] }"#).unwrap();
+        assert_eq!(to_value(&value).unwrap(), value);
+        assert_eq!(from_value::<Value>(value.clone()).unwrap(), value);
+
+        // The kept text isn't passed through serde.
+        let options = ::ParseOptions {
+            keep_number_text: true,
+            ..::ParseOptions::default()
+        };
+        let value = ::parse_with_options("[1.50, 100000000000000000000]", &options).unwrap();
+        assert_eq!(value.serialize(), "[1.50,100000000000000000000]");
+        assert_eq!(to_string(&value).unwrap(), "[1.5,100000000000000000000]");
+    }
+
+    #[test]
+    fn test_map_keys() {
+        let mut map = BTreeMap::new();
+        map.insert(1, "one");
+        map.insert(2, "two");
+        assert_eq!(to_string(&map).unwrap(), r#"{"1":"one","2":"two"}"#);
+
+        let mut map = BTreeMap::new();
+        map.insert(vec![1], 1);
+        assert!(to_value(&map).is_err());
+    }
+
+    #[test]
+    fn test_error_path() {
+        let json = r#"[
+            { "id": 1, "name": "a", "tags": [], "shape": "Point", "note": null },
+            { "id": 2, "name": "b", "tags": ["x", 3], "shape": "Point", "note": null }
+        ]"#;
+        let err = from_str::<Vec<Item>>(json).expect_err("Expected an error.");
+        assert_eq!(
+            err.path().map(|p| p.to_string()),
+            Some("/1/tags/1".to_string())
+        );
+
+        let json = r#"[{ "id": 1, "tags": [], "shape": "Point", "note": null }]"#;
+        let err = from_str::<Vec<Item>>(json).expect_err("Expected an error.");
+        assert_eq!(err.path().map(|p| p.to_string()), Some("/0".to_string()));
+        assert_eq!(err.to_string(), "missing field `name` at \"/0\"");
+
+        let json =
+            r#"{ "id": 1, "name": "a", "tags": [], "shape": { "Circle": "r" }, "note": null }"#;
+        let err = from_str::<Item>(json).expect_err("Expected an error.");
+        assert_eq!(
+            err.path().map(|p| p.to_string()),
+            Some("/shape/Circle".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        match from_str::<Item>("{") {
+            Err(SerdeError::Parse(_)) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
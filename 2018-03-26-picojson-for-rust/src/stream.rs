@@ -0,0 +1,235 @@
+//! Streams of values, such as [JSON Lines](https://jsonlines.org)
+//! and concatenated JSON.
+
+use std::io::{self, Read, Write};
+
+use {parse_input, serialize, DefaultParseContext, Error, ErrorKind, Input, ParseOptions};
+use {ReadChars, Value};
+
+/// Iterates over values read from a stream one by one.
+///
+/// Values are separated by whitespace, so that both JSON Lines
+/// (one value per line) and concatenated JSON are accepted.
+/// Positions of errors are relative to the beginning of the stream.
+/// After a syntax error, the rest of the line is skipped and parsing continues.
+/// After an I/O error, invalid utf-8 or a value exceeding `max_document_size`,
+/// the iteration ends.
+pub struct StreamParser<R: Read> {
+    input: Input<ReadChars<R>>,
+    done: bool,
+}
+
+impl<R: Read> StreamParser<R> {
+    pub fn new(reader: R) -> StreamParser<R> {
+        StreamParser::with_options(reader, ParseOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: ParseOptions) -> StreamParser<R> {
+        StreamParser {
+            input: Input::with_options(ReadChars::new(reader), options),
+            done: false,
+        }
+    }
+
+    /// Takes an error occurred while reading, which ends the iteration.
+    fn take_read_error(&mut self) -> Option<Error> {
+        let position = self.input.position;
+        let kind = self.input.chars.error.take()?;
+        self.done = true;
+        Some(Error::new(kind, position))
+    }
+
+    /// Skips characters until the next line.
+    fn skip_line(&mut self) {
+        loop {
+            match self.input.getc() {
+                Some('\n') => return,
+                Some(_) => continue,
+                None => {
+                    self.input.ungetc();
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for StreamParser<R> {
+    type Item = Result<Value, Error>;
+
+    fn next(&mut self) -> Option<Result<Value, Error>> {
+        if self.done {
+            return None;
+        }
+
+        // Limits apply to each value.
+        self.input.document_start = self.input.position.offset;
+        let skipped = self.input.skip_ws();
+        if skipped && self.input.getc().is_none() {
+            self.input.ungetc();
+            self.done = true;
+            return self.take_read_error().map(Err);
+        }
+        self.input.ungetc();
+
+        let mut value = Value::Null;
+        if skipped && parse_input(&mut DefaultParseContext::new(&mut value), &mut self.input) {
+            return Some(Ok(value));
+        }
+
+        if let Some(err) = self.take_read_error() {
+            return Some(Err(err));
+        }
+
+        let position = self.input.position;
+        let err = self
+            .input
+            .error
+            .take()
+            .unwrap_or_else(|| Error::new(ErrorKind::Aborted, position));
+        self.skip_line();
+        Some(Err(err))
+    }
+}
+
+/// Parses values from a stream of JSON Lines or concatenated JSON.
+/// See `StreamParser` for details.
+pub fn parse_stream<R: Read>(reader: R) -> StreamParser<R> {
+    StreamParser::new(reader)
+}
+
+/// Writes values as JSON Lines, i.e., one compact value per line.
+pub struct StreamWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> StreamWriter<W> {
+    pub fn new(writer: W) -> StreamWriter<W> {
+        StreamWriter { writer }
+    }
+
+    /// Writes a value followed by a newline.
+    pub fn write(&mut self, value: &Value) -> io::Result<()> {
+        serialize(value, &mut self.writer)?;
+        self.writer.write_all(b"\n")
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_string;
+
+    fn parse_all(source: &str) -> Vec<Result<String, (String, usize, usize)>> {
+        parse_stream(source.as_bytes())
+            .map(|result| {
+                result
+                    .map(|value| value.serialize())
+                    .map_err(|err| (err.kind().to_string(), err.line(), err.column()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let source = "{\"a\": 1}\n[2, 3]\n\n\"four\"\n";
+        assert_eq!(
+            parse_all(source),
+            vec![
+                Ok(r#"{"a":1}"#.to_string()),
+                Ok("[2,3]".to_string()),
+                Ok(r#""four""#.to_string()),
+            ]
+        );
+        assert!(parse_all("").is_empty());
+        assert!(parse_all(" \n ").is_empty());
+    }
+
+    #[test]
+    fn test_concatenated() {
+        let source = "{\"a\":\n  1\n}{}[] 1 \"x\"null";
+        assert_eq!(
+            parse_all(source),
+            vec![
+                Ok(r#"{"a":1}"#.to_string()),
+                Ok("{}".to_string()),
+                Ok("[]".to_string()),
+                Ok("1".to_string()),
+                Ok(r#""x""#.to_string()),
+                Ok("null".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let source = "1\n[2,, 3] 4\n5\n\"6";
+        assert_eq!(
+            parse_all(source),
+            vec![
+                Ok("1".to_string()),
+                Err(("Unexpected ',', expected value".to_string(), 2, 4)),
+                Ok("5".to_string()),
+                Err(("Unterminated string".to_string(), 4, 1)),
+            ]
+        );
+
+        // Invalid utf-8 ends the stream.
+        let source: &[u8] = b"1\n\xFF\n2\n";
+        let results = parse_stream(source).collect::<Vec<_>>();
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[1].as_ref().unwrap_err().kind(),
+            &ErrorKind::InvalidUtf8
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let options = ParseOptions {
+            max_document_size: Some(8),
+            ..ParseOptions::default()
+        };
+        let source = "[1, 2]\n[1, 2, 3]\n[4]\n";
+        let results = StreamParser::with_options(source.as_bytes(), options)
+            .map(|result| result.map_err(|err| (err.kind().to_string(), err.line())))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            vec![
+                Ok(parse_string("[1, 2]").unwrap()),
+                Err(("Document larger than 8 bytes".to_string(), 2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_writer() {
+        let mut writer = StreamWriter::new(Vec::new());
+        let values = vec![
+            parse_string(r#"{"a": [1, "x\ny"]}"#).unwrap(),
+            parse_string("null").unwrap(),
+        ];
+        for value in &values {
+            writer.write(value).unwrap();
+        }
+        let output = writer.into_inner();
+        assert_eq!(
+            String::from_utf8(output.clone()).unwrap(),
+            "{\"a\":[1,\"x\\ny\"]}\nnull\n"
+        );
+
+        let parsed = parse_stream(&output[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(parsed, values);
+    }
+}
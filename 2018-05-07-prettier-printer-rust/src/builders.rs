@@ -1,11 +1,18 @@
-use std;
+use utils::clone_doc;
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub enum Doc {
     Source(String),
     Computed(ComputedDoc),
 }
 
+/// Clones without recursion, since docs can be nested deeply.
+impl Clone for Doc {
+    fn clone(&self) -> Doc {
+        clone_doc(self)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum ComputedDoc {
     Concat {
@@ -35,6 +42,11 @@ pub enum ComputedDoc {
     LineSuffix {
         contents: Box<Doc>,
     },
+    /// Contents with a label, which printers can inspect to know what kind of doc it is.
+    Label {
+        label: String,
+        contents: Box<Doc>,
+    },
     LineSuffixBoundary,
     BreakParent,
     Line(LineKind),
@@ -85,18 +97,10 @@ impl LineKind {
     }
 }
 
+#[derive(Default)]
 pub struct GroupSetting {
-    should_break: Option<bool>,
-    expanded_states: Option<Vec<Doc>>,
-}
-
-impl std::default::Default for GroupSetting {
-    fn default() -> GroupSetting {
-        GroupSetting {
-            should_break: None,
-            expanded_states: None,
-        }
-    }
+    pub should_break: Option<bool>,
+    pub expanded_states: Option<Vec<Doc>>,
 }
 
 // unnecessary in rust
@@ -138,8 +142,19 @@ pub fn group(contents: Doc) -> Doc {
     group_ex(contents, GroupSetting::default())
 }
 
-pub fn conditional_group(_options: Vec<Doc>, _setting: Option<GroupSetting>) -> Doc {
-    unimplemented!()
+/// Creates a group which tries each state in order and picks the first one that fits.
+/// If none fits, the last (most expanded) state is printed in break mode.
+pub fn conditional_group(states: Vec<Doc>, setting: Option<GroupSetting>) -> Doc {
+    let contents = match states.first() {
+        None => panic!("States can't be empty."),
+        Some(x) => x.clone(),
+    };
+
+    let setting = GroupSetting {
+        expanded_states: Some(states),
+        ..setting.unwrap_or_default()
+    };
+    group_ex(contents, setting)
 }
 
 pub fn fill(parts: Vec<Doc>) -> Doc {
@@ -168,8 +183,8 @@ pub fn fill(parts: Vec<Doc>) -> Doc {
 
 pub fn if_break(break_contents: Option<Doc>, flat_contents: Option<Doc>) -> Doc {
     com(ComputedDoc::IfBreak {
-        break_contents: break_contents.map(|x| Box::new(x)),
-        flat_contents: flat_contents.map(|x| Box::new(x)),
+        break_contents: break_contents.map(Box::new),
+        flat_contents: flat_contents.map(Box::new),
     })
 }
 
@@ -179,6 +194,14 @@ pub fn line_suffix(contents: Doc) -> Doc {
     })
 }
 
+/// Marks the doc with a label, e.g. `"member-chain"`. It's printed as the contents.
+pub fn label<T: ToString>(label: T, contents: Doc) -> Doc {
+    com(ComputedDoc::Label {
+        label: label.to_string(),
+        contents: Box::new(contents),
+    })
+}
+
 pub const LINE_SUFFIX_BOUNDARY: Doc = Doc::Computed(ComputedDoc::LineSuffixBoundary);
 pub const BREAK_PARENT: Doc = Doc::Computed(ComputedDoc::BreakParent);
 
@@ -186,7 +209,11 @@ pub const LINE: Doc = Doc::Computed(ComputedDoc::Line(LineKind::Space));
 
 pub const SOFT_LINE: Doc = Doc::Computed(ComputedDoc::Line(LineKind::Soft));
 
-// FIXME: HARD_LINE, LITERAL_LINE
+/// Line which is always printed as a newline. Enclosing groups break.
+pub const HARD_LINE: Doc = Doc::Computed(ComputedDoc::Line(LineKind::Hard));
+
+/// Hard line without indentation.
+pub const LITERAL_LINE: Doc = Doc::Computed(ComputedDoc::Line(LineKind::Literal));
 
 pub const CURSOR: Doc = Doc::Computed(ComputedDoc::Cursor);
 
@@ -209,6 +236,7 @@ pub fn join(separator: Doc, parts: Vec<Doc>) -> Doc {
 }
 
 pub fn add_alignment_to_doc(contents: Doc, size: usize, tab_width: usize) -> Doc {
+    let tab_width = tab_width.max(1);
     let mut aligned = contents;
     if size > 0 {
         // Use indent to add (hard or soft) tabs as possible.
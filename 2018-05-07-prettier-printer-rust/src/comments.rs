@@ -0,0 +1,483 @@
+//! Attaches comments to syntax nodes and prints them, like Prettier's `comments.js`.
+//!
+//! Each comment is attached to a node as one of:
+//!
+//! - leading: printed before the node, e.g. a comment on the line above a statement,
+//! - trailing: printed after the node, e.g. a comment at the end of the line,
+//! - dangling: printed inside the node which has no child to attach to, e.g. `f(/* c */)`.
+//!
+//! Trailing comments are printed as line suffixes, so that they stay at the end of lines
+//! even if separators such as `,` are printed after the node.
+//! Printers should put `LINE_SUFFIX_BOUNDARY` where pending comments must be flushed,
+//! e.g. before text which can't contain newlines.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use builders::{concat, indent, join, line_suffix, Doc, BREAK_PARENT, HARD_LINE};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CommentKind {
+    /// Comment to the end of the line, e.g. `// ...`.
+    Line,
+
+    /// Comment which can be in a line, e.g. `/* ... */`.
+    Block,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Comment {
+    /// Range of the comment in the source, including delimiters.
+    pub range: Range<usize>,
+    pub kind: CommentKind,
+}
+
+/// Node of a syntax tree.
+pub trait Node {
+    /// Range of the node in the source. It must contain the ranges of children.
+    fn range(&self) -> Range<usize>;
+
+    /// Children in order of appearance.
+    fn children(&self) -> Vec<&Self>;
+}
+
+enum Placement {
+    Leading,
+    Trailing,
+    Dangling,
+}
+
+#[derive(Default, Debug)]
+struct NodeComments<'a> {
+    leading: Vec<&'a Comment>,
+    trailing: Vec<&'a Comment>,
+    dangling: Vec<&'a Comment>,
+}
+
+/// Comments attached to nodes, identified by address.
+pub struct Comments<'a, N: 'a> {
+    source: &'a str,
+    comments: &'a [Comment],
+    nodes: HashMap<*const N, NodeComments<'a>>,
+}
+
+fn is_space(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn is_newline(c: char) -> bool {
+    c == '\n' || c == '\r'
+}
+
+/// Whether the rest of the line from the offset is blank.
+fn has_newline(source: &str, offset: usize) -> bool {
+    source[offset..]
+        .chars()
+        .find(|&c| !is_space(c))
+        .is_none_or(is_newline)
+}
+
+/// Whether the line is blank before the offset.
+fn has_newline_backward(source: &str, offset: usize) -> bool {
+    source[..offset]
+        .chars()
+        .rev()
+        .find(|&c| !is_space(c))
+        .is_none_or(is_newline)
+}
+
+fn is_previous_line_empty(source: &str, offset: usize) -> bool {
+    let mut lines = source[..offset].rsplit('\n').skip(1);
+    lines.next().is_some_and(|line| line.trim().is_empty())
+}
+
+/// Skips spaces and a newline.
+fn skip_newline(source: &str, offset: usize) -> usize {
+    let rest = source[offset..].trim_start_matches(is_space);
+    let rest = rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix(is_newline))
+        .unwrap_or(rest);
+    source.len() - rest.len()
+}
+
+/// Finds the deepest node enclosing the range,
+/// and children of it just before and after the range.
+fn decorate<'n, N: Node>(
+    root: &'n N,
+    range: &Range<usize>,
+) -> (&'n N, Option<&'n N>, Option<&'n N>) {
+    let mut enclosing = root;
+    'descend: loop {
+        let mut preceding = None;
+        for child in enclosing.children() {
+            let child_range = child.range();
+            if child_range.start <= range.start && range.end <= child_range.end {
+                enclosing = child;
+                continue 'descend;
+            }
+
+            if child_range.end <= range.start {
+                preceding = Some(child);
+            } else if range.end <= child_range.start {
+                return (enclosing, preceding, Some(child));
+            }
+        }
+        return (enclosing, preceding, None);
+    }
+}
+
+/// Attaches comments to the nodes in the tree.
+///
+/// A comment on its own line is leading of the following node.
+/// A comment at the end of a line is trailing of the preceding node.
+/// Otherwise, a comment is leading of the following node if only whitespaces are between them.
+pub fn attach_comments<'a, N: Node>(
+    source: &'a str,
+    root: &'a N,
+    comments: &'a [Comment],
+) -> Comments<'a, N> {
+    let mut nodes: HashMap<*const N, NodeComments<'a>> = HashMap::new();
+
+    for comment in comments {
+        let (enclosing, preceding, following) = decorate(root, &comment.range);
+
+        let own_line = has_newline_backward(source, comment.range.start);
+        let end_of_line = has_newline(source, comment.range.end);
+        let leads_following = |following: &N| {
+            source[comment.range.end..following.range().start]
+                .trim()
+                .is_empty()
+        };
+
+        let (node, placement) = match (preceding, following) {
+            (_, Some(following)) if own_line => (following, Placement::Leading),
+            (Some(preceding), _) if own_line || end_of_line => (preceding, Placement::Trailing),
+            (Some(_), Some(following)) if leads_following(following) => {
+                (following, Placement::Leading)
+            }
+            (Some(preceding), _) => (preceding, Placement::Trailing),
+            (None, Some(following)) => (following, Placement::Leading),
+            (None, None) => (enclosing, Placement::Dangling),
+        };
+
+        let comments = nodes.entry(node as *const N).or_default();
+        match placement {
+            Placement::Leading => comments.leading.push(comment),
+            Placement::Trailing => comments.trailing.push(comment),
+            Placement::Dangling => comments.dangling.push(comment),
+        }
+    }
+
+    Comments {
+        source,
+        comments,
+        nodes,
+    }
+}
+
+impl<'a, N: Node> Comments<'a, N> {
+    fn get(&self, node: &N) -> Option<&NodeComments<'a>> {
+        self.nodes.get(&(node as *const N))
+    }
+
+    fn print_comment(&self, comment: &Comment) -> Doc {
+        Doc::Source(self.source[comment.range.clone()].trim_end().to_owned())
+    }
+
+    fn print_leading_comment(&self, comment: &Comment) -> Doc {
+        let contents = self.print_comment(comment);
+        let end = comment.range.end;
+
+        let mut parts = vec![contents];
+        if comment.kind == CommentKind::Block && !has_newline(self.source, end) {
+            parts.push(Doc::Source(" ".to_owned()));
+        } else {
+            parts.push(HARD_LINE);
+
+            // Keep a blank line between the comment and the node.
+            if has_newline(self.source, skip_newline(self.source, end)) {
+                parts.push(HARD_LINE);
+            }
+        }
+        concat(parts)
+    }
+
+    fn print_trailing_comment(&self, comment: &Comment) -> Doc {
+        let contents = self.print_comment(comment);
+        let start = comment.range.start;
+
+        if has_newline_backward(self.source, start) {
+            // Keep a comment on its own line, with a blank line before it if any.
+            let mut parts = vec![HARD_LINE];
+            if is_previous_line_empty(self.source, start) {
+                parts.push(HARD_LINE);
+            }
+            parts.push(contents);
+            return line_suffix(concat(parts));
+        }
+
+        let parts = concat(vec![Doc::Source(" ".to_owned()), contents]);
+        match comment.kind {
+            CommentKind::Block => parts,
+            // Nothing can follow a line comment in the line.
+            CommentKind::Line => concat(vec![line_suffix(parts), BREAK_PARENT]),
+        }
+    }
+
+    /// Prints leading and trailing comments around the printed node.
+    pub fn print_comments(&self, node: &N, printed: Doc) -> Doc {
+        let comments = match self.get(node) {
+            Some(comments) => comments,
+            None => return printed,
+        };
+
+        let mut parts = vec![];
+        for comment in &comments.leading {
+            parts.push(self.print_leading_comment(comment));
+        }
+        parts.push(printed);
+        for comment in &comments.trailing {
+            parts.push(self.print_trailing_comment(comment));
+        }
+        concat(parts)
+    }
+
+    pub fn has_dangling_comments(&self, node: &N) -> bool {
+        self.get(node)
+            .is_some_and(|comments| !comments.dangling.is_empty())
+    }
+
+    /// Prints dangling comments of the node, one per line.
+    /// They are indented on new lines unless `same_indent`.
+    pub fn print_dangling_comments(&self, node: &N, same_indent: bool) -> Doc {
+        let dangling = match self.get(node) {
+            Some(comments) if !comments.dangling.is_empty() => &comments.dangling,
+            _ => return Doc::Source(String::new()),
+        };
+
+        let mut parts = vec![join(
+            HARD_LINE,
+            dangling
+                .iter()
+                .map(|comment| self.print_comment(comment))
+                .collect(),
+        )];
+        if dangling.last().unwrap().kind == CommentKind::Line {
+            parts.push(BREAK_PARENT);
+        }
+
+        let printed = concat(parts);
+        if same_indent {
+            printed
+        } else {
+            indent(concat(vec![HARD_LINE, printed]))
+        }
+    }
+
+    /// Whether a blank line follows the line where the offset is,
+    /// skipping comments in the line.
+    pub fn is_next_line_empty(&self, offset: usize) -> bool {
+        let mut offset = offset;
+        loop {
+            offset = self.source.len() - self.source[offset..].trim_start_matches(is_space).len();
+            match self
+                .comments
+                .iter()
+                .find(|comment| comment.range.start == offset)
+            {
+                Some(comment) => offset = comment.range.end,
+                None => break,
+            }
+        }
+
+        if !self.source[offset..].starts_with(is_newline) {
+            return false;
+        }
+        has_newline(self.source, skip_newline(self.source, offset))
+    }
+
+    /// Joins printed statements with newlines, keeping a blank line between statements if any.
+    pub fn join_statements(&self, statements: &[&N], printed: Vec<Doc>) -> Doc {
+        let count = statements.len();
+        let mut parts = vec![];
+        for (i, (statement, printed)) in statements.iter().zip(printed).enumerate() {
+            parts.push(printed);
+            if i + 1 < count {
+                parts.push(HARD_LINE);
+                if self.is_next_line_empty(statement.range().end) {
+                    parts.push(HARD_LINE);
+                }
+            }
+        }
+        concat(parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builders::{group, LINE, SOFT_LINE};
+    use printer::{print_doc_to_string, Options};
+
+    enum Kind {
+        Program,
+        Leaf,
+        Call(&'static str),
+    }
+
+    struct TestNode {
+        kind: Kind,
+        range: Range<usize>,
+        children: Vec<TestNode>,
+    }
+
+    impl Node for TestNode {
+        fn range(&self) -> Range<usize> {
+            self.range.clone()
+        }
+
+        fn children(&self) -> Vec<&TestNode> {
+            self.children.iter().collect()
+        }
+    }
+
+    fn s<T: ToString>(source: T) -> Doc {
+        Doc::Source(source.to_string())
+    }
+
+    fn node(source: &str, text: &str, kind: Kind, children: Vec<TestNode>) -> TestNode {
+        let start = source.find(text).unwrap();
+        TestNode {
+            kind,
+            range: start..start + text.len(),
+            children,
+        }
+    }
+
+    fn print_node(source: &str, comments: &Comments<TestNode>, node: &TestNode) -> Doc {
+        let printed = match node.kind {
+            Kind::Program => {
+                let statements = node.children.iter().collect::<Vec<_>>();
+                let printed = statements
+                    .iter()
+                    .map(|statement| print_node(source, comments, statement))
+                    .collect();
+                concat(vec![
+                    comments.join_statements(&statements, printed),
+                    HARD_LINE,
+                ])
+            }
+            Kind::Leaf => s(&source[node.range.clone()]),
+            Kind::Call(name) if node.children.is_empty() => concat(vec![
+                s(name),
+                s("("),
+                comments.print_dangling_comments(node, true),
+                s(");"),
+            ]),
+            Kind::Call(name) => {
+                let args = node
+                    .children
+                    .iter()
+                    .map(|arg| print_node(source, comments, arg))
+                    .collect();
+                group(concat(vec![
+                    s(name),
+                    s("("),
+                    indent(concat(vec![
+                        SOFT_LINE,
+                        join(concat(vec![s(","), LINE]), args),
+                    ])),
+                    SOFT_LINE,
+                    s(");"),
+                ]))
+            }
+        };
+        comments.print_comments(node, printed)
+    }
+
+    #[test]
+    fn test_comments() {
+        let source =
+            "// header\n\na = 1; // one\n/* pre */ b = 2;\n\nf(/* none */);\ng(x, // arg\n  y);\n";
+        let comment = |text: &str, kind: CommentKind| {
+            let start = source.find(text).unwrap();
+            Comment {
+                range: start..start + text.len(),
+                kind,
+            }
+        };
+        let comment_list = vec![
+            comment("// header", CommentKind::Line),
+            comment("// one", CommentKind::Line),
+            comment("/* pre */", CommentKind::Block),
+            comment("/* none */", CommentKind::Block),
+            comment("// arg", CommentKind::Line),
+        ];
+
+        let g = node(
+            source,
+            "g(x, // arg\n  y);",
+            Kind::Call("g"),
+            vec![
+                node(source, "x", Kind::Leaf, vec![]),
+                node(source, "y", Kind::Leaf, vec![]),
+            ],
+        );
+        let root = TestNode {
+            kind: Kind::Program,
+            range: 0..source.len(),
+            children: vec![
+                node(source, "a = 1;", Kind::Leaf, vec![]),
+                node(source, "b = 2;", Kind::Leaf, vec![]),
+                node(source, "f(/* none */);", Kind::Call("f"), vec![]),
+                g,
+            ],
+        };
+
+        let comments = attach_comments(source, &root, &comment_list);
+        let [a, b, f, g] = [
+            &root.children[0],
+            &root.children[1],
+            &root.children[2],
+            &root.children[3],
+        ];
+        let x = &g.children[0];
+        assert_eq!(comments.get(a).unwrap().leading, vec![&comment_list[0]]);
+        assert_eq!(comments.get(a).unwrap().trailing, vec![&comment_list[1]]);
+        assert_eq!(comments.get(b).unwrap().leading, vec![&comment_list[2]]);
+        assert!(comments.has_dangling_comments(f));
+        assert!(!comments.has_dangling_comments(g));
+        assert_eq!(comments.get(x).unwrap().trailing, vec![&comment_list[4]]);
+
+        let doc = print_node(source, &comments, &root);
+        let output = print_doc_to_string(&doc, &Options::default());
+        assert_eq!(
+            output.formatted,
+            "// header\n\na = 1; // one\n/* pre */ b = 2;\n\nf(/* none */);\ng(\n    x, // arg\n    y\n);\n"
+        );
+    }
+
+    #[test]
+    fn test_is_next_line_empty() {
+        let source = "a; /* c */ // d\n\nb;\nc;";
+        let comment_list = vec![
+            Comment {
+                range: 3..10,
+                kind: CommentKind::Block,
+            },
+            Comment {
+                range: 11..15,
+                kind: CommentKind::Line,
+            },
+        ];
+        let root = TestNode {
+            kind: Kind::Program,
+            range: 0..source.len(),
+            children: vec![],
+        };
+        let comments = attach_comments(source, &root, &comment_list);
+        assert!(comments.is_next_line_empty(2));
+        assert!(!comments.is_next_line_empty(source.find("b;").unwrap() + 2));
+    }
+}
@@ -1,15 +1,272 @@
-use builders::Doc;
+//! Renders docs as builder calls like Prettier's `--debug-print-doc`, e.g.
+//! `group(concat(["a", indent(concat([softline, "b"]))]))`.
 
-fn flatten_doc(doc: &Doc) -> &Doc {
-    // FIXME: port
-    doc
+use builders::{concat, group, indent, join, AlignSize, ComputedDoc, Doc, LineKind};
+use builders::{LINE, SOFT_LINE};
+use printer::{print_doc_to_string, Options};
+use utils::{drop_doc, map_doc_owned, walk, GroupChildren, Step};
+
+fn source<T: ToString>(source: T) -> Doc {
+    Doc::Source(source.to_string())
+}
+
+fn push_part(parts: &mut Vec<Doc>, part: Doc) {
+    if let Doc::Source(ref source) = part {
+        if source.is_empty() {
+            return;
+        }
+
+        if let Some(Doc::Source(last)) = parts.last_mut() {
+            last.push_str(source);
+            return;
+        }
+    }
+    parts.push(part);
+}
+
+/// Flattens nested concats and joins adjacent strings.
+fn flatten_doc(doc: &Doc) -> Doc {
+    map_doc_owned(doc, |doc| match doc {
+        Doc::Computed(ComputedDoc::Concat { parts }) => {
+            let mut flattened = vec![];
+            for part in parts {
+                match part {
+                    Doc::Computed(ComputedDoc::Concat { parts }) => {
+                        for part in parts {
+                            push_part(&mut flattened, part);
+                        }
+                    }
+                    part => push_part(&mut flattened, part),
+                }
+            }
+
+            match flattened.len() {
+                0 => source(""),
+                1 => flattened.pop().unwrap(),
+                _ => concat(flattened),
+            }
+        }
+        doc => doc,
+    })
+}
+
+/// Creates a doc of a bracketed list, e.g. `f(a, b)`, which breaks into lines if it doesn't fit.
+fn bracketed(open: &str, items: Vec<Doc>, close: &str) -> Doc {
+    group(concat(vec![
+        source(open),
+        indent(concat(vec![
+            SOFT_LINE,
+            join(concat(vec![source(","), LINE]), items),
+        ])),
+        SOFT_LINE,
+        source(close),
+    ]))
+}
+
+/// Creates a doc of a call. A single argument hugs the parentheses, e.g. `indent(concat([`.
+fn call(name: &str, mut args: Vec<Doc>) -> Doc {
+    if args.len() == 1 {
+        let arg = args.pop().unwrap();
+        return concat(vec![source(name), source("("), arg, source(")")]);
+    }
+    bracketed(&format!("{}(", name), args, ")")
+}
+
+fn list(items: Vec<Doc>) -> Doc {
+    bracketed("[", items, "]")
 }
 
-fn print_doc(doc: &Doc) -> String {
-    format!("{:?}", doc)
+/// A printed doc. Fills are kept as their parts until the parent is printed,
+/// so that a chain of fills is printed as one.
+enum Printed {
+    Doc(Doc),
+    Fill(Vec<Doc>),
 }
 
-// FIXME: use Debug trait
+impl Printed {
+    fn into_doc(self) -> Doc {
+        match self {
+            Printed::Doc(doc) => doc,
+            Printed::Fill(parts) => call("fill", vec![list(parts)]),
+        }
+    }
+}
+
+/// Prints the doc with its children already printed,
+/// in the order `walk` visits them with `GroupChildren::ExpandedStates`.
+fn print_computed_doc(doc: &ComputedDoc, children: Vec<Printed>) -> Printed {
+    let mut children = children.into_iter().map(Printed::into_doc);
+    let mut child = || children.next().unwrap();
+
+    let doc = match doc {
+        ComputedDoc::Concat { .. } => call("concat", vec![list(children.collect())]),
+        ComputedDoc::Indent { .. } => call("indent", vec![child()]),
+        ComputedDoc::Align {
+            n: AlignSize::NegativeInfinity,
+            ..
+        } => call("dedentToRoot", vec![child()]),
+        ComputedDoc::Align {
+            n: AlignSize::Number(n),
+            ..
+        } => call("align", vec![source(n), child()]),
+        ComputedDoc::ReseltAlign { n, .. } => {
+            let n = match n {
+                AlignSize::NegativeInfinity => source("-Infinity"),
+                AlignSize::Number(n) => source(n),
+            };
+            call("reseltAlign", vec![n, child()])
+        }
+        ComputedDoc::Group {
+            breaks,
+            expanded_states,
+            ..
+        } => {
+            let mut args = match expanded_states {
+                Some(_) => vec![list(children.collect())],
+                None => vec![child()],
+            };
+            if *breaks {
+                args.push(source("{ shouldBreak: true }"));
+            }
+
+            let name = if expanded_states.is_some() {
+                "conditionalGroup"
+            } else {
+                "group"
+            };
+            call(name, args)
+        }
+        // Fills are printed by `print_doc` to unroll their chain.
+        ComputedDoc::Fill(_) => unreachable!(),
+        ComputedDoc::IfBreak {
+            break_contents,
+            flat_contents,
+        } => {
+            let mut args = vec![match break_contents {
+                Some(_) => child(),
+                None => source("\"\""),
+            }];
+            if flat_contents.is_some() {
+                args.push(child());
+            }
+            call("ifBreak", args)
+        }
+        ComputedDoc::LineSuffix { .. } => call("lineSuffix", vec![child()]),
+        ComputedDoc::Label { label, .. } => {
+            call("label", vec![source(format!("{:?}", label)), child()])
+        }
+        ComputedDoc::LineSuffixBoundary => source("lineSuffixBoundary"),
+        ComputedDoc::BreakParent => source("breakParent"),
+        ComputedDoc::Line(LineKind::Space) => source("line"),
+        ComputedDoc::Line(LineKind::Soft) => source("softline"),
+        ComputedDoc::Line(LineKind::Hard) => source("hardline"),
+        ComputedDoc::Line(LineKind::Literal) => source("literalline"),
+        ComputedDoc::Cursor => source("cursor"),
+    };
+    Printed::Doc(doc)
+}
+
+/// Prints the doc as builder calls without recursion, so that deeply nested docs can be printed.
+fn print_doc(doc: &Doc) -> Doc {
+    // Printed docs, which the parent takes at exit, and where the children of each doc start.
+    let mut printed: Vec<Printed> = Vec::new();
+    let mut starts = Vec::new();
+    walk(doc, GroupChildren::ExpandedStates, |step| {
+        match step {
+            Step::Enter(_) => starts.push(printed.len()),
+            Step::Exit(doc) => {
+                let children = printed.split_off(starts.pop().unwrap());
+                printed.push(match doc {
+                    Doc::Source(ref text) => Printed::Doc(source(format!("{:?}", text))),
+                    Doc::Computed(ComputedDoc::Fill(_)) => {
+                        // Unroll the chain of fills.
+                        let mut children = children.into_iter();
+                        let mut parts = vec![
+                            children.next().unwrap().into_doc(),
+                            children.next().unwrap().into_doc(),
+                        ];
+                        match children.next().unwrap() {
+                            Printed::Fill(rest) => parts.extend(rest),
+                            Printed::Doc(rest) => parts.push(rest),
+                        }
+                        Printed::Fill(parts)
+                    }
+                    Doc::Computed(doc) => print_computed_doc(doc, children),
+                });
+            }
+        }
+        true
+    });
+    printed.pop().unwrap().into_doc()
+}
+
+/// Renders the doc as builder calls, to see why a layout goes wrong.
+/// Nested concats are flattened and adjacent strings are joined.
 pub fn print_doc_to_debug(doc: &Doc) -> String {
-    print_doc(flatten_doc(doc))
+    let flattened = flatten_doc(doc);
+    let doc = print_doc(&flattened);
+    drop_doc(flattened);
+    let formatted = print_doc_to_string(&doc, &Options::default()).formatted;
+    drop_doc(doc);
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builders::{
+        align, conditional_group, fill, group_ex, if_break, label, GroupSetting, BREAK_PARENT,
+        HARD_LINE,
+    };
+
+    #[test]
+    fn test_print_doc_to_debug() {
+        let doc = group(concat(vec![
+            source("a"),
+            concat(vec![source("b\n"), source(""), concat(vec![source("c")])]),
+            indent(concat(vec![SOFT_LINE, source("d")])),
+            concat(vec![]),
+        ]));
+        assert_eq!(
+            print_doc_to_debug(&doc),
+            r#"group(concat(["ab\nc", indent(concat([softline, "d"]))]))"#
+        );
+
+        let doc = concat(vec![
+            align(AlignSize::Number(2), HARD_LINE),
+            if_break(None, Some(source(","))),
+            group_ex(
+                BREAK_PARENT,
+                GroupSetting {
+                    should_break: Some(true),
+                    ..GroupSetting::default()
+                },
+            ),
+            conditional_group(vec![source("x"), LINE], None),
+            fill(vec![source("a"), LINE, source("b"), LINE, source("c")]),
+            label("member-chain", source("x")),
+        ]);
+        assert_eq!(
+            print_doc_to_debug(&doc),
+            r#"concat([
+    align(2, hardline),
+    ifBreak("", ","),
+    group(breakParent, { shouldBreak: true }),
+    conditionalGroup(["x", line]),
+    fill(["a", line, "b", line, "c"]),
+    label("member-chain", "x")
+])"#
+        );
+    }
+
+    #[test]
+    fn test_deep_doc() {
+        let mut doc = concat(vec![source("a"), concat(vec![source("b")])]);
+        for _ in 0..100_000 {
+            doc = indent(doc);
+        }
+        let expected = format!("{}\"ab\"{}", "indent(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(print_doc_to_debug(&doc), expected);
+        drop_doc(doc);
+    }
 }
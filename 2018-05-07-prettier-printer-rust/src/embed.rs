@@ -0,0 +1,131 @@
+//! Embeds docs of a language into docs of another language,
+//! e.g. JSON in a string literal of a host language.
+//!
+//! The embedded doc is printed as a part of the host doc, so that it's indented
+//! and fitted to the print width from where it's put.
+
+use builders::{join, label, Doc, LITERAL_LINE};
+use utils::{map_doc, strip_trailing_hard_line};
+
+/// Label of docs created by `embed`.
+pub const EMBED_LABEL: &str = "embed";
+
+/// Replaces line breaks (`\n`, `\r\n` or `\r`) in text of the doc with `line`.
+/// With `LITERAL_LINE`, lines in the text are kept as is, e.g. in multiline string literals;
+/// with `HARD_LINE`, they're indented as the doc around.
+pub fn replace_end_of_line(doc: &Doc, line: Doc) -> Doc {
+    map_doc(doc, &|doc| match doc {
+        Doc::Source(source) if source.contains(['\n', '\r']) => {
+            let lines = source
+                .split("\r\n")
+                .flat_map(|line| line.split(['\n', '\r']))
+                .map(|line| Doc::Source(line.to_owned()))
+                .collect();
+            join(line.clone(), lines)
+        }
+        _ => doc.clone(),
+    })
+}
+
+/// Creates a doc of the embedded text to put into the host doc.
+///
+/// `text_to_doc` creates a doc of the text, e.g. `formatters::json::text_to_doc`.
+/// Line breaks in text of the doc, such as in multiline strings, are kept as is
+/// and the trailing hard line, if any, is removed.
+/// The result is labeled `EMBED_LABEL`.
+pub fn embed<F, E>(text: &str, text_to_doc: F) -> Result<Doc, E>
+where
+    F: FnOnce(&str) -> Result<Doc, E>,
+{
+    let doc = strip_trailing_hard_line(&text_to_doc(text)?);
+    Ok(label(EMBED_LABEL, replace_end_of_line(&doc, LITERAL_LINE)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builders::{concat, indent, HARD_LINE};
+    use formatters::{json, toml};
+    use printer::{print_doc_to_string, Options};
+    use utils::get_label;
+
+    fn s<T: ToString>(source: T) -> Doc {
+        Doc::Source(source.to_string())
+    }
+
+    /// Puts the doc into a raw string literal in a function.
+    fn host(embedded: Doc) -> Doc {
+        concat(vec![
+            s("fn main() {"),
+            indent(concat(vec![
+                HARD_LINE,
+                s("let data = r#\""),
+                indent(concat(vec![HARD_LINE, embedded])),
+                HARD_LINE,
+                s("\"#;"),
+            ])),
+            HARD_LINE,
+            s("}"),
+        ])
+    }
+
+    #[test]
+    fn test_embed() {
+        let text = r#"{"name": "embed", "tags": ["json", "in", "string"]}"#;
+        let embedded = embed(text, json::text_to_doc).unwrap();
+        assert_eq!(get_label(&embedded), Some(EMBED_LABEL));
+
+        // The embedded doc fits the rest of the line after the host indentation.
+        let options = Options {
+            print_width: 48,
+            tab_width: 2,
+            ..Options::default()
+        };
+        let output = print_doc_to_string(&host(embedded), &options);
+        assert_eq!(
+            output.formatted,
+            r##"fn main() {
+  let data = r#"
+    {
+      "name": "embed",
+      "tags": ["json", "in", "string"]
+    }
+  "#;
+}"##
+        );
+
+        assert!(embed("[1,", json::text_to_doc).is_err());
+        assert!(embed("a = ", toml::text_to_doc).is_err());
+    }
+
+    #[test]
+    fn test_embed_multiline_string() {
+        let text = "a = \"\"\"\nline1\r\nline2\n\"\"\"\nb = 1\n";
+        let embedded = embed(text, toml::text_to_doc).unwrap();
+        let output = print_doc_to_string(&host(embedded), &Options::default());
+        assert_eq!(
+            output.formatted,
+            r##"fn main() {
+    let data = r#"
+        a = """
+line1
+line2
+"""
+        b = 1
+    "#;
+}"##
+        );
+    }
+
+    #[test]
+    fn test_replace_end_of_line() {
+        let doc = indent(concat(vec![
+            s("x = "),
+            replace_end_of_line(&s("a\r\n  b\rc\n"), LITERAL_LINE),
+            HARD_LINE,
+            replace_end_of_line(&s("d\ne"), HARD_LINE),
+        ]));
+        let output = print_doc_to_string(&doc, &Options::default());
+        assert_eq!(output.formatted, "x = a\n  b\nc\n\n    d\n    e");
+    }
+}
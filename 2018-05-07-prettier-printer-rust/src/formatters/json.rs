@@ -0,0 +1,345 @@
+//! JSON formatter.
+//!
+//! Objects and arrays are printed in a line if they fit; otherwise each entry is put on its own line.
+//! Arrays of numbers are filled instead, so that many numbers are put per line.
+//! Numbers and strings are kept as written, e.g. `1e10` or `"é"`, and so are duplicate keys.
+//! A blank line between entries is kept, which puts the entries on their own lines.
+
+use std::error;
+use std::fmt;
+
+use builders::{concat, Doc, HARD_LINE, LINE, SOFT_LINE};
+use formatters::{bracketed, print};
+use printer::Options;
+
+/// Maximum nesting depth of arrays and objects, so that parsing doesn't overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Represents an error of JSON syntax. Lines and columns start with 1.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParseError {
+    pub message: &'static str,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.message, self.line, self.column
+        )
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// Whether the whitespace contains a blank line, i.e., two line breaks.
+fn is_blank_line_in(whitespace: &str) -> bool {
+    whitespace
+        .replace("\r\n", "\n")
+        .matches(['\n', '\r'])
+        .count()
+        >= 2
+}
+
+/// Parses JSON into a doc directly, keeping the text of numbers, strings and keys.
+struct Parser<'a> {
+    source: &'a str,
+    offset: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &'static str) -> Result<T, ParseError> {
+        let before = &self.source[..self.offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Err(ParseError {
+            message,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        })
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.offset).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.offset += 1;
+        }
+    }
+
+    /// Skips whitespace and the byte if it's next.
+    fn eat(&mut self, b: u8) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(b) {
+            self.offset += 1;
+            return true;
+        }
+        false
+    }
+
+    fn eat_digits(&mut self) -> bool {
+        let start = self.offset;
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.offset += 1;
+        }
+        self.offset > start
+    }
+
+    fn text(&self, start: usize) -> Doc {
+        Doc::Source(self.source[start..self.offset].to_owned())
+    }
+
+    fn literal(&mut self, word: &'static str) -> Result<Doc, ParseError> {
+        if !self.source[self.offset..].starts_with(word) {
+            return self.error("Expected a value");
+        }
+        self.offset += word.len();
+        Ok(Doc::Source(word.to_owned()))
+    }
+
+    fn number(&mut self) -> Result<Doc, ParseError> {
+        let start = self.offset;
+        self.eat(b'-');
+        match self.peek() {
+            Some(b'0') => self.offset += 1,
+            Some(b'1'..=b'9') => {
+                self.eat_digits();
+            }
+            _ => return self.error("Invalid number"),
+        }
+        if self.peek() == Some(b'.') {
+            self.offset += 1;
+            if !self.eat_digits() {
+                return self.error("Invalid number");
+            }
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.offset += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.offset += 1;
+            }
+            if !self.eat_digits() {
+                return self.error("Invalid number");
+            }
+        }
+        Ok(self.text(start))
+    }
+
+    fn string(&mut self) -> Result<Doc, ParseError> {
+        let start = self.offset;
+        self.offset += 1;
+        loop {
+            match self.peek() {
+                None => return self.error("Unterminated string"),
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    self.offset += 1;
+                    match self.peek() {
+                        Some(b'"') | Some(b'\\') | Some(b'/') | Some(b'b') | Some(b'f')
+                        | Some(b'n') | Some(b'r') | Some(b't') => {}
+                        Some(b'u') => {
+                            let hex = self.source.as_bytes().get(self.offset + 1..self.offset + 5);
+                            if !hex.is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)) {
+                                return self.error("Invalid escape sequence");
+                            }
+                            self.offset += 4;
+                        }
+                        _ => return self.error("Invalid escape sequence"),
+                    }
+                }
+                Some(b) if b < b' ' => return self.error("Control character in string"),
+                Some(_) => {}
+            }
+            self.offset += 1;
+        }
+        self.offset += 1;
+        Ok(self.text(start))
+    }
+
+    /// Parses items or members in brackets, separated by commas,
+    /// with whether a blank line follows the comma after each.
+    fn items<F>(&mut self, close: u8, mut item: F) -> Result<Vec<(Doc, bool)>, ParseError>
+    where
+        F: FnMut(&mut Parser<'a>) -> Result<Doc, ParseError>,
+    {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return self.error("Nested too deep");
+        }
+        self.offset += 1;
+
+        let mut items = vec![];
+        if !self.eat(close) {
+            loop {
+                let doc = item(self)?;
+                if self.eat(close) {
+                    items.push((doc, false));
+                    break;
+                }
+                if !self.eat(b',') {
+                    return self.error("Expected ',' or a closing bracket");
+                }
+                let start = self.offset;
+                self.skip_whitespace();
+                items.push((doc, is_blank_line_in(&self.source[start..self.offset])));
+            }
+        }
+
+        self.depth -= 1;
+        Ok(items)
+    }
+
+    fn member(&mut self) -> Result<Doc, ParseError> {
+        self.skip_whitespace();
+        if self.peek() != Some(b'"') {
+            return self.error("Expected a string key");
+        }
+        let key = self.string()?;
+        if !self.eat(b':') {
+            return self.error("Expected ':'");
+        }
+        Ok(concat(vec![
+            key,
+            Doc::Source(": ".to_owned()),
+            self.value()?,
+        ]))
+    }
+
+    fn value(&mut self) -> Result<Doc, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => {
+                let members = self.items(b'}', Parser::member)?;
+                if members.is_empty() {
+                    return Ok(Doc::Source("{}".to_owned()));
+                }
+                Ok(bracketed("{", LINE, members, "}", false))
+            }
+            Some(b'[') => {
+                let mut should_fill = true;
+                let items = self.items(b']', |parser| {
+                    parser.skip_whitespace();
+                    should_fill &= matches!(parser.peek(), Some(b'-') | Some(b'0'..=b'9'));
+                    parser.value()
+                })?;
+                if items.is_empty() {
+                    return Ok(Doc::Source("[]".to_owned()));
+                }
+                Ok(bracketed("[", SOFT_LINE, items, "]", should_fill))
+            }
+            Some(b'"') => self.string(),
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            Some(b't') => self.literal("true"),
+            Some(b'f') => self.literal("false"),
+            Some(b'n') => self.literal("null"),
+            _ => self.error("Expected a value"),
+        }
+    }
+}
+
+/// Parses JSON and creates a doc of it, e.g. to `embed` it.
+pub fn text_to_doc(source: &str) -> Result<Doc, ParseError> {
+    let mut parser = Parser {
+        source,
+        offset: 0,
+        depth: 0,
+    };
+    let doc = parser.value()?;
+    parser.skip_whitespace();
+    if parser.offset != source.len() {
+        return parser.error("Unexpected trailing characters");
+    }
+    Ok(doc)
+}
+
+/// Parses and formats JSON. The result ends with a newline.
+pub fn format_json(source: &str, options: &Options) -> Result<String, ParseError> {
+    let doc = concat(vec![text_to_doc(source)?, HARD_LINE]);
+    Ok(print(&doc, source, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use formatters::run_golden_tests;
+
+    #[test]
+    fn test_format_json() {
+        let options = Options {
+            print_width: 24,
+            ..Options::default()
+        };
+        let format = |source: &str| format_json(source, &options).unwrap();
+
+        assert_eq!(format("[]"), "[]\n");
+        assert_eq!(
+            format(r#"{"a":[1,2],"b":{}}"#),
+            "{ \"a\": [1, 2], \"b\": {} }\n"
+        );
+        assert_eq!(
+            format(r#"{"alpha":1,"beta":"あ\n"}"#),
+            "{\n    \"alpha\": 1,\n    \"beta\": \"あ\\n\"\n}\n"
+        );
+        assert_eq!(format("[1,\n\n\n2]"), "[\n    1,\n\n    2\n]\n");
+        assert_eq!(format("[1,\r\n2]"), "[1, 2]\n");
+        assert_eq!(format("[1,\r\n\r\n2]"), "[\n    1,\n\n    2\n]\n");
+        assert!(format_json("[1,", &options).is_err());
+    }
+
+    #[test]
+    fn test_keep_values() {
+        let options = Options::default();
+        let format = |source: &str| format_json(source, &options).unwrap();
+
+        assert_eq!(
+            format("[12345678901234567890123,-0,1e10,1.50E-3]"),
+            "[12345678901234567890123, -0, 1e10, 1.50E-3]\n"
+        );
+        assert_eq!(format(r#"{"a":1,"a":2}"#), "{ \"a\": 1, \"a\": 2 }\n");
+        assert_eq!(format(r#""é\u00e9\/""#), "\"é\\u00e9\\/\"\n");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |source: &str| text_to_doc(source).unwrap_err().to_string();
+
+        assert_eq!(error(""), "Expected a value at line 1, column 1");
+        assert_eq!(
+            error("[1,\n 2 3]"),
+            "Expected ',' or a closing bracket at line 2, column 4"
+        );
+        assert_eq!(error("{1: 2}"), "Expected a string key at line 1, column 2");
+        assert_eq!(
+            error("[01]"),
+            "Expected ',' or a closing bracket at line 1, column 3"
+        );
+        assert_eq!(error("[1.]"), "Invalid number at line 1, column 4");
+        assert_eq!(
+            error(r#""\x""#),
+            "Invalid escape sequence at line 1, column 3"
+        );
+        assert_eq!(
+            error("\"a\tb\""),
+            "Control character in string at line 1, column 3"
+        );
+        assert_eq!(error("nul"), "Expected a value at line 1, column 1");
+        assert_eq!(
+            error("{} {}"),
+            "Unexpected trailing characters at line 1, column 4"
+        );
+        assert_eq!(
+            error(&"[".repeat(100_000)),
+            "Nested too deep at line 1, column 129"
+        );
+    }
+
+    #[test]
+    fn test_golden() {
+        run_golden_tests("tests/golden/json", "json", format_json);
+    }
+}
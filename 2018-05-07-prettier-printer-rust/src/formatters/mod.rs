@@ -0,0 +1,104 @@
+//! Formatters of some languages built on the doc builders.
+
+pub mod json;
+pub mod toml;
+
+use builders::{concat, fill, group, indent, Doc, HARD_LINE, LINE};
+use printer::{print_doc_to_string, Options};
+
+/// Creates a doc of a bracketed list, e.g. `[1, 2]`, which breaks into lines if it doesn't fit.
+/// `line` is put inside the brackets, e.g. `LINE` for `{ a }` and `SOFT_LINE` for `[a]`.
+/// Items are filled as many as possible per line if `should_fill`.
+/// Each item comes with whether a blank line follows it in the source, which is kept
+/// and puts the items on their own lines.
+fn bracketed(
+    open: &str,
+    line: Doc,
+    items: Vec<(Doc, bool)>,
+    close: &str,
+    should_fill: bool,
+) -> Doc {
+    let separator = concat(vec![Doc::Source(",".to_owned()), LINE]);
+    let blank_line_separator = concat(vec![Doc::Source(",".to_owned()), HARD_LINE, HARD_LINE]);
+    let mut parts = Vec::with_capacity(items.len() * 2);
+    let mut blank_line = false;
+    for (i, (item, is_blank_line_next)) in items.into_iter().enumerate() {
+        if i != 0 {
+            parts.push(if blank_line {
+                blank_line_separator.clone()
+            } else {
+                separator.clone()
+            });
+        }
+        parts.push(item);
+        blank_line = is_blank_line_next;
+    }
+    let items = if should_fill {
+        fill(parts)
+    } else {
+        concat(parts)
+    };
+
+    group(concat(vec![
+        Doc::Source(open.to_owned()),
+        indent(concat(vec![line.clone(), items])),
+        line,
+        Doc::Source(close.to_owned()),
+    ]))
+}
+
+/// Prints a doc of the source, resolving the end of line from the source.
+fn print(doc: &Doc, source: &str, options: &Options) -> String {
+    let options = Options {
+        end_of_line: options.end_of_line.resolve(source),
+        ..options.clone()
+    };
+    print_doc_to_string(doc, &options).formatted
+}
+
+/// Runs golden tests: formats each input file in the directory
+/// and compares the result with the output file next to it.
+/// Set `UPDATE_GOLDEN=1` to overwrite output files instead.
+#[cfg(test)]
+fn run_golden_tests<E: ::std::fmt::Debug>(
+    dir: &str,
+    extension: &str,
+    format: fn(&str, &Options) -> Result<String, E>,
+) {
+    use std::fs;
+    use std::path::Path;
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+    let input_suffix = format!(".input.{}", extension);
+    let update = ::std::env::var_os("UPDATE_GOLDEN").is_some();
+    let options = Options {
+        print_width: 40,
+        ..Options::default()
+    };
+
+    let mut count = 0;
+    for entry in fs::read_dir(&dir).unwrap() {
+        let input_path = entry.unwrap().path();
+        let name = input_path.file_name().unwrap().to_str().unwrap().to_owned();
+        if !name.ends_with(&input_suffix) {
+            continue;
+        }
+
+        let output_name = name.replace(&input_suffix, &format!(".output.{}", extension));
+        let output_path = input_path.with_file_name(output_name);
+        let input = fs::read_to_string(&input_path).unwrap();
+        let actual = format(&input, &options).unwrap();
+
+        if update {
+            fs::write(&output_path, &actual).unwrap();
+        } else {
+            let expected = fs::read_to_string(&output_path).unwrap();
+            assert_eq!(actual, expected, "{}", name);
+
+            // Formatting is idempotent.
+            assert_eq!(format(&actual, &options).unwrap(), expected, "{}", name);
+        }
+        count += 1;
+    }
+    assert!(count > 0, "No golden files in {:?}", dir);
+}
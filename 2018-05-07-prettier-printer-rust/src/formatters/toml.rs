@@ -0,0 +1,342 @@
+//! TOML formatter.
+//!
+//! Key/value pairs come first in each table, followed by `[a.b]` sections of sub-tables
+//! and `[[a.b]]` sections of arrays of tables in order of appearance, separated by blank lines.
+//! A blank line between key/value pairs or comments is kept.
+//! Keys, strings and numbers are kept as written, e.g. `'literal'` or `0xff`.
+//! Arrays are printed like JSON arrays. Inline tables are always in a line,
+//! because TOML doesn't allow newlines in them.
+//! Comments are kept on their own lines or at the end of lines as written.
+//! Arrays containing comments are printed one item per line.
+
+use toml_edit::{self, Array, DocumentMut, InlineTable, Item, Key, RawString, Table, Value};
+
+use builders::{concat, indent, join, Doc, HARD_LINE, SOFT_LINE};
+use formatters::{bracketed, print};
+use printer::Options;
+
+fn source<T: ToString>(source: T) -> Doc {
+    Doc::Source(source.to_string())
+}
+
+/// Gets comments in the whitespace, with whether each is on the first line,
+/// i.e., follows the preceding token on the same line.
+fn comments(raw: Option<&RawString>) -> Vec<(bool, String)> {
+    raw.and_then(RawString::as_str)
+        .unwrap_or("")
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i == 0, line.trim()))
+        .filter(|(_, line)| line.starts_with('#'))
+        .map(|(first, line)| (first, line.to_owned()))
+        .collect()
+}
+
+/// Gets comments in the whitespace before something on its own line, with `None` for
+/// blank lines. Consecutive blank lines are collapsed into one.
+fn leading_lines(raw: Option<&RawString>) -> Vec<Option<String>> {
+    let raw = raw.and_then(RawString::as_str).unwrap_or("");
+    let count = raw.split('\n').count();
+    let mut lines: Vec<Option<String>> = vec![];
+    for (i, line) in raw.split('\n').enumerate() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            lines.push(Some(line.to_owned()));
+        } else if line.is_empty() && i + 1 < count && lines.last() != Some(&None) {
+            // The last line is the one of what follows, so it isn't blank.
+            lines.push(None);
+        }
+    }
+    lines
+}
+
+/// Creates a doc of comments which are put on their own lines before something,
+/// keeping a blank line between them if any. A blank line before the first comment
+/// is kept only if `keep_first_blank_line` is set.
+fn leading_comments(raw: Option<&RawString>, keep_first_blank_line: bool) -> Doc {
+    let mut lines = leading_lines(raw);
+    if !keep_first_blank_line && lines.first() == Some(&None) {
+        lines.remove(0);
+    }
+    lines_to_doc(lines)
+}
+
+fn lines_to_doc(lines: Vec<Option<String>>) -> Doc {
+    let lines = lines
+        .into_iter()
+        .map(|line| match line {
+            Some(comment) => concat(vec![source(comment), HARD_LINE]),
+            None => HARD_LINE,
+        })
+        .collect();
+    concat(lines)
+}
+
+/// Creates a doc of the comment at the end of a line, if any.
+fn trailing_comment(raw: Option<&RawString>) -> Doc {
+    let comments = comments(raw)
+        .into_iter()
+        .map(|(_, comment)| source(format!(" {}", comment)))
+        .collect();
+    concat(comments)
+}
+
+fn has_comments(items: &Array) -> bool {
+    !comments(Some(items.trailing())).is_empty()
+        || items.iter().any(|item| {
+            !comments(item.decor().prefix()).is_empty()
+                || !comments(item.decor().suffix()).is_empty()
+        })
+}
+
+/// Creates a doc of an array containing comments, which puts each item on its own line.
+fn commented_array_to_doc(items: &Array) -> Doc {
+    // Parts of each line. The first line is the opening bracket.
+    let mut lines = vec![vec![source("[")]];
+    let push_comments = |lines: &mut Vec<Vec<Doc>>, raw: Option<&RawString>| {
+        for (first, comment) in comments(raw) {
+            if first {
+                lines
+                    .last_mut()
+                    .unwrap()
+                    .push(source(format!(" {}", comment)));
+            } else {
+                lines.push(vec![source(comment)]);
+            }
+        }
+    };
+
+    for item in items.iter() {
+        push_comments(&mut lines, item.decor().prefix());
+        lines.push(vec![value_to_doc(item, false), source(",")]);
+        push_comments(&mut lines, item.decor().suffix());
+    }
+    push_comments(&mut lines, Some(items.trailing()));
+
+    let mut lines = lines.into_iter().map(concat);
+    let open = lines.next().unwrap();
+    concat(vec![
+        open,
+        indent(concat(vec![HARD_LINE, join(HARD_LINE, lines.collect())])),
+        HARD_LINE,
+        source("]"),
+    ])
+}
+
+fn path_to_string(path: &[&Key]) -> String {
+    path.iter()
+        .map(|key| key.display_repr())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn inline_table_to_doc(table: &InlineTable) -> Doc {
+    let entries = table
+        .get_values()
+        .into_iter()
+        .map(|(path, value)| pair_to_doc(&path, value, true, false))
+        .collect::<Vec<_>>();
+    if entries.is_empty() {
+        return source("{}");
+    }
+    concat(vec![
+        source("{ "),
+        join(source(", "), entries),
+        source(" }"),
+    ])
+}
+
+/// Creates a doc of a value. Values in inline tables are never broken into lines.
+pub fn value_to_doc(value: &Value, in_inline_table: bool) -> Doc {
+    match value {
+        Value::String(value) => source(value.display_repr()),
+        Value::Integer(value) => source(value.display_repr()),
+        Value::Float(value) => source(value.display_repr()),
+        Value::Boolean(value) => source(value.display_repr()),
+        Value::Datetime(value) => source(value.display_repr()),
+        Value::Array(items) if has_comments(items) => commented_array_to_doc(items),
+        Value::Array(items) if items.is_empty() => source("[]"),
+        Value::Array(items) if in_inline_table => {
+            let items = items.iter().map(|item| value_to_doc(item, true)).collect();
+            concat(vec![source("["), join(source(", "), items), source("]")])
+        }
+        Value::Array(items) => {
+            let should_fill = items
+                .iter()
+                .all(|item| item.is_integer() || item.is_float());
+            let items = items
+                .iter()
+                .map(|item| (value_to_doc(item, false), false))
+                .collect();
+            bracketed("[", SOFT_LINE, items, "]", should_fill)
+        }
+        Value::InlineTable(table) => inline_table_to_doc(table),
+    }
+}
+
+fn pair_to_doc(path: &[&Key], value: &Value, in_inline_table: bool, is_first: bool) -> Doc {
+    let mut parts = path
+        .iter()
+        .map(|key| leading_comments(key.leaf_decor().prefix(), !is_first))
+        .collect::<Vec<_>>();
+    parts.extend(vec![
+        source(path_to_string(path)),
+        source(" = "),
+        value_to_doc(value, in_inline_table),
+        trailing_comment(value.decor().suffix()),
+    ]);
+    concat(parts)
+}
+
+/// Creates docs of key/value pairs in the table, including dotted keys.
+/// Blank lines between them are kept.
+fn pairs_to_docs(table: &Table) -> Vec<Doc> {
+    table
+        .get_values()
+        .into_iter()
+        .enumerate()
+        .map(|(i, (path, value))| pair_to_doc(&path, value, false, i == 0))
+        .collect()
+}
+
+fn section_to_doc(header: String, table: &Table) -> Doc {
+    let header = concat(vec![
+        leading_comments(table.decor().prefix(), false),
+        source(header),
+        trailing_comment(table.decor().suffix()),
+    ]);
+    let pairs = pairs_to_docs(table);
+    if pairs.is_empty() {
+        return header;
+    }
+    concat(vec![header, HARD_LINE, join(HARD_LINE, pairs)])
+}
+
+/// Adds docs of sections in the table with their positions in the document.
+fn add_sections<'a>(
+    table: &'a Table,
+    path: &mut Vec<&'a Key>,
+    sections: &mut Vec<(Option<usize>, Doc)>,
+) {
+    for (name, item) in table.iter() {
+        let key = table.key(name).unwrap();
+
+        match item {
+            Item::Table(table) if !table.is_dotted() => {
+                path.push(key);
+
+                // Tables only with sub-tables are defined implicitly.
+                if !table.is_implicit() || !table.get_values().is_empty() {
+                    let header = format!("[{}]", path_to_string(path));
+                    sections.push((table.position(), section_to_doc(header, table)));
+                }
+                add_sections(table, path, sections);
+
+                path.pop();
+            }
+            Item::ArrayOfTables(tables) => {
+                path.push(key);
+
+                for table in tables.iter() {
+                    let header = format!("[[{}]]", path_to_string(path));
+                    sections.push((table.position(), section_to_doc(header, table)));
+                    add_sections(table, path, sections);
+                }
+
+                path.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn document_to_doc(document: &DocumentMut) -> Doc {
+    let mut blocks = vec![];
+
+    let pairs = pairs_to_docs(document.as_table());
+    if !pairs.is_empty() {
+        blocks.push(join(HARD_LINE, pairs));
+    }
+
+    // Sections are printed in order of appearance, even if a sub-table comes after others.
+    let mut sections = vec![];
+    add_sections(document.as_table(), &mut vec![], &mut sections);
+    sections.sort_by_key(|(position, _)| *position);
+    blocks.extend(sections.into_iter().map(|(_, section)| section));
+
+    let mut trailing = leading_lines(Some(document.trailing()));
+    if trailing.last() == Some(&None) {
+        trailing.pop();
+    }
+    if blocks.is_empty() && trailing.first() == Some(&None) {
+        trailing.remove(0);
+    }
+    let trailing = lines_to_doc(trailing);
+    if blocks.is_empty() {
+        return trailing;
+    }
+    concat(vec![
+        join(concat(vec![HARD_LINE, HARD_LINE]), blocks),
+        HARD_LINE,
+        trailing,
+    ])
+}
+
+/// Parses a TOML document and creates a doc of it, e.g. to `embed` it.
+pub fn text_to_doc(source: &str) -> Result<Doc, toml_edit::TomlError> {
+    let document = source.parse::<DocumentMut>()?;
+    Ok(document_to_doc(&document))
+}
+
+/// Parses and formats a TOML document. The result ends with a newline unless empty.
+pub fn format_toml(source: &str, options: &Options) -> Result<String, toml_edit::TomlError> {
+    Ok(print(&text_to_doc(source)?, source, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use formatters::run_golden_tests;
+
+    #[test]
+    fn test_format_toml() {
+        let options = Options {
+            print_width: 20,
+            ..Options::default()
+        };
+        let format = |source: &str| format_toml(source, &options).unwrap();
+
+        assert_eq!(format(""), "");
+        assert_eq!(format("a=1\n'b c'=[]"), "a = 1\n'b c' = []\n");
+        assert_eq!(
+            format("[x.y]\n[[z]]\nw={p=[1,2]}\nv.u=0x10"),
+            "[x.y]\n\n[[z]]\nw = { p = [1, 2] }\nv.u = 0x10\n"
+        );
+        assert_eq!(format("[a]\n[b]\n[a.c]\n"), "[a]\n\n[b]\n\n[a.c]\n");
+        assert!(format_toml("a = ", &options).is_err());
+    }
+
+    #[test]
+    fn test_comments() {
+        let format = |source: &str| format_toml(source, &Options::default()).unwrap();
+
+        assert_eq!(
+            format("# top\na = 1 # trailing\n[t]\n# c\nb = 2\n"),
+            "# top\na = 1 # trailing\n\n[t]\n# c\nb = 2\n"
+        );
+        assert_eq!(format("# only\n"), "# only\n");
+        assert_eq!(
+            format("\na = 1\n\n\nb = 2\n# c\n\n# d\nc = 3\n\n"),
+            "a = 1\n\nb = 2\n# c\n\n# d\nc = 3\n"
+        );
+        assert_eq!(
+            format("a = [1, # one\n2]"),
+            "a = [\n    1, # one\n    2,\n]\n"
+        );
+    }
+
+    #[test]
+    fn test_golden() {
+        run_golden_tests("tests/golden/toml", "toml", format_toml);
+    }
+}
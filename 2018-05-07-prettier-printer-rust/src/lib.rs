@@ -1,7 +1,15 @@
+extern crate toml_edit;
+extern crate unicode_segmentation;
+extern crate unicode_width;
+
 pub mod builders;
+pub mod comments;
 pub mod debug;
+pub mod embed;
+pub mod formatters;
 pub mod printer;
 pub mod utils;
+pub mod width;
 
 // FIXME: re-export?
 
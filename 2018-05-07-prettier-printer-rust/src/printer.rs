@@ -1,36 +1,110 @@
 #![allow(unused_imports)]
 
 use builders::Fill;
-use builders::{concat, fill, AlignSize, ComputedDoc, Doc, LineKind, CURSOR};
+use builders::{
+    add_alignment_to_doc, concat, fill, AlignSize, ComputedDoc, Doc, LineKind, CURSOR, HARD_LINE,
+};
 use std;
 use std::borrow::Cow;
 use std::io::Write;
+use std::ops::Range;
+use utils::{drop_doc, propagate_breaks};
+use width::TextWidth;
+
+/// Line break sequence to print.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EndOfLine {
+    Lf,
+    Crlf,
+    Cr,
+
+    /// The first line break in the input, or `Lf` if none.
+    Auto,
+}
+
+impl EndOfLine {
+    /// Finds the first line break in the input.
+    pub fn detect(input: &str) -> EndOfLine {
+        match input.find(['\r', '\n']) {
+            Some(i) if input[i..].starts_with("\r\n") => EndOfLine::Crlf,
+            Some(i) if input[i..].starts_with('\r') => EndOfLine::Cr,
+            _ => EndOfLine::Lf,
+        }
+    }
+
+    /// Resolves `Auto` to the line break in the input.
+    pub fn resolve(self, input: &str) -> EndOfLine {
+        match self {
+            EndOfLine::Auto => EndOfLine::detect(input),
+            end_of_line => end_of_line,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EndOfLine::Lf | EndOfLine::Auto => "\n",
+            EndOfLine::Crlf => "\r\n",
+            EndOfLine::Cr => "\r",
+        }
+    }
+}
 
 // FIXME: rename to config
+#[derive(Clone, Debug)]
 pub struct Options {
-    print_width: usize,
-    new_line: Option<String>,
+    pub print_width: usize,
+
+    /// Whether to indent with tabs rather than spaces.
+    /// As in Prettier, an align takes a tab when an indent follows it; otherwise it stays spaces,
+    /// so that continuation lines line up at any tab width.
+    pub use_tabs: bool,
+
+    /// Number of columns a tab takes and an indent is worth. `0` is treated as `1`.
+    pub tab_width: usize,
+
+    /// `Auto` is detected from the source in `print_doc_in_range`; otherwise it's `Lf`.
+    pub end_of_line: EndOfLine,
+
+    /// How to measure text and indentation.
+    pub text_width: TextWidth,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            print_width: 80,
+            use_tabs: false,
+            tab_width: 4,
+            end_of_line: EndOfLine::Lf,
+            text_width: TextWidth::default(),
+        }
+    }
 }
 
 pub struct Output {
     pub formatted: String,
 
-    // FIXME: in bytes or chars?
+    /// Offset of the cursor in `formatted` in bytes, if the doc has a cursor placeholder.
     pub cursor: Option<usize>,
 }
 
 #[derive(Clone, Copy)]
 struct Indent {
     indent: usize,
+    /// Columns of all aligns, used with spaces.
     spaces: usize,
+    /// Aligns followed by an indent, each printed as a tab with tabs.
     tabs: usize,
+    /// Aligns after the last indent and their columns, printed as spaces with tabs.
+    trailing_aligns: usize,
+    trailing_spaces: usize,
 }
 
 impl std::fmt::Debug for Indent {
     fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         formatter.write_fmt(format_args!(
-            "Indent({}, {}, {})",
-            self.indent, self.spaces, self.tabs
+            "Indent({}, {}, {}, {}, {})",
+            self.indent, self.spaces, self.tabs, self.trailing_aligns, self.trailing_spaces
         ))
     }
 }
@@ -62,12 +136,17 @@ fn root_indent() -> Indent {
         indent: 0,
         spaces: 0,
         tabs: 0,
+        trailing_aligns: 0,
+        trailing_spaces: 0,
     }
 }
 
 fn make_indent(indent: Indent) -> Indent {
     Indent {
         indent: indent.indent + 1,
+        tabs: indent.tabs + indent.trailing_aligns,
+        trailing_aligns: 0,
+        trailing_spaces: 0,
         ..indent
     }
 }
@@ -75,10 +154,12 @@ fn make_indent(indent: Indent) -> Indent {
 fn make_align(indent: Indent, n: AlignSize) -> Indent {
     match n {
         AlignSize::NegativeInfinity => root_indent(),
+        AlignSize::Number(0) => indent,
         AlignSize::Number(n) => Indent {
-            indent: indent.indent,
             spaces: indent.spaces + n,
-            tabs: indent.tabs + (if n != 0 { 1 } else { 0 }),
+            trailing_aligns: indent.trailing_aligns + 1,
+            trailing_spaces: indent.trailing_spaces + n,
+            ..indent
         },
     }
 }
@@ -91,7 +172,15 @@ fn break_if(mode: Mode, breaks: bool) -> Mode {
     }
 }
 
-fn fits(next: Command, rest_commands: &[Command], width: i32, must_be_flat: bool) -> bool {
+static HARD_LINE_DOC: Doc = HARD_LINE;
+
+fn fits(
+    next: Command,
+    rest_commands: &[Command],
+    width: i32,
+    must_be_flat: bool,
+    text_width: TextWidth,
+) -> bool {
     // always equal to rest_commands.len() ?
     let mut rest_index = rest_commands.len();
     let mut commands = vec![next];
@@ -112,26 +201,31 @@ fn fits(next: Command, rest_commands: &[Command], width: i32, must_be_flat: bool
 
         match doc {
             Doc::Source(ref source) => {
-                width -= source.len() as i32;
+                width -= text_width.measure(source) as i32;
             }
             Doc::Computed(ComputedDoc::Concat { parts }) => {
                 for part in parts.iter().rev() {
                     commands.push(Command {
-                        indent: indent.clone(),
+                        indent,
                         mode,
                         doc: part,
                     })
                 }
             }
             Doc::Computed(ComputedDoc::Fill(fill)) => {
-                for part in vec![&fill.second, &fill.whitespace, &fill.first] {
+                for part in [&fill.second, &fill.whitespace, &fill.first] {
                     commands.push(Command {
-                        indent: indent.clone(),
+                        indent,
                         mode,
                         doc: part,
                     });
                 }
             }
+            Doc::Computed(ComputedDoc::Label { contents, .. }) => commands.push(Command {
+                indent,
+                mode,
+                doc: contents,
+            }),
             Doc::Computed(ComputedDoc::Indent { contents }) => commands.push(Command {
                 indent: make_indent(indent),
                 mode,
@@ -143,28 +237,32 @@ fn fits(next: Command, rest_commands: &[Command], width: i32, must_be_flat: bool
                 doc: contents,
             }),
             Doc::Computed(ComputedDoc::Group {
-                breaks, contents, ..
+                breaks,
+                contents,
+                expanded_states,
             }) => {
-                if must_be_flat && *breaks {
+                let breaks = *breaks;
+                if must_be_flat && breaks {
                     return false;
                 }
 
-                commands.push(Command {
-                    indent,
-                    mode: break_if(mode, *breaks),
-                    doc: contents,
-                })
+                let mode = break_if(mode, breaks);
+                let doc = match (mode, expanded_states) {
+                    (Mode::Break, Some(states)) => most_expanded(states, contents),
+                    _ => contents,
+                };
+                commands.push(Command { indent, mode, doc })
             }
             Doc::Computed(ComputedDoc::IfBreak {
                 break_contents,
                 flat_contents,
             }) => match (mode, break_contents, flat_contents) {
-                (Mode::Break, &Some(ref contents), _) => commands.push(Command {
+                (Mode::Break, Some(contents), _) => commands.push(Command {
                     indent,
                     mode,
                     doc: contents,
                 }),
-                (Mode::Flat, _, &Some(ref contents)) => commands.push(Command {
+                (Mode::Flat, _, Some(contents)) => commands.push(Command {
                     indent,
                     mode,
                     doc: contents,
@@ -173,59 +271,151 @@ fn fits(next: Command, rest_commands: &[Command], width: i32, must_be_flat: bool
             },
             Doc::Computed(ComputedDoc::Line(kind)) => match (mode, kind) {
                 (Mode::Break, _) => return true,
+                (Mode::Flat, kind) if kind.is_hard() => return true,
                 (Mode::Flat, LineKind::Space) => width -= 1,
                 _ => {}
             },
+            // Contents with a forced break can't be printed flat.
+            Doc::Computed(ComputedDoc::BreakParent) if mode == Mode::Flat => return false,
             _ => {}
         }
     }
     false
 }
 
+/// Gets the last state of a conditional group, which is printed if no state fits.
+fn most_expanded<'a>(expanded_states: &'a [Doc], contents: &'a Doc) -> &'a Doc {
+    expanded_states.last().unwrap_or(contents)
+}
+
+/// Removes trailing whitespaces of the current line.
+fn trim(out: &mut [Cow<str>]) {
+    for part in out.iter_mut().rev() {
+        let len = part.trim_end_matches([' ', '\t']).len();
+        match part {
+            Cow::Borrowed(source) => *source = &source[..len],
+            Cow::Owned(source) => source.truncate(len),
+        }
+
+        if len != 0 {
+            break;
+        }
+    }
+}
+
+/// Measures text in a line, where tabs advance to the next tab stop.
+fn measure_column(text: &str, options: &Options) -> usize {
+    let tab_width = options.tab_width.max(1);
+    let mut column = 0;
+    for (i, segment) in text.split('\t').enumerate() {
+        if i != 0 {
+            column += tab_width - column % tab_width;
+        }
+        column += options.text_width.measure(segment);
+    }
+    column
+}
+
 pub fn print_doc_to_string(doc: &Doc, options: &Options) -> Output {
-    let (width, new_line) = {
-        (
-            options.print_width as i32,
-            options.new_line.clone().unwrap_or("\n".to_owned()),
-        )
+    print_doc(doc, options, 0, options.end_of_line.as_str())
+}
+
+/// Prints a doc as a replacement of the range of the source,
+/// e.g., to format the subtree of a syntax tree which the range spans.
+///
+/// The doc is printed continuing from the column where the range starts
+/// and indented as deep as the line. Text out of the range is kept as is.
+/// The cursor comes from the placeholder in the doc if any;
+/// otherwise `cursor` (an offset in the source) is moved along with the text around it.
+pub fn print_doc_in_range(
+    doc: &Doc,
+    options: &Options,
+    source: &str,
+    range: Range<usize>,
+    cursor: Option<usize>,
+) -> Output {
+    let line = &source[source[..range.start]
+        .rfind(['\n', '\r'])
+        .map_or(0, |i| i + 1)..range.start];
+    let line_indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+    let doc = add_alignment_to_doc(
+        doc.clone(),
+        measure_column(line_indent, options),
+        options.tab_width,
+    );
+    let column = measure_column(line, options);
+    let new_line = options.end_of_line.resolve(source).as_str();
+    let printed = print_doc(&doc, options, column as i32, new_line);
+    drop_doc(doc);
+
+    let cursor = match (printed.cursor, cursor) {
+        (Some(cursor), _) => Some(range.start + cursor),
+        (None, Some(cursor)) if cursor < range.start => Some(cursor),
+        (None, Some(cursor)) if cursor < range.end => Some(range.start),
+        (None, Some(cursor)) => Some(cursor - range.end + range.start + printed.formatted.len()),
+        (None, None) => None,
     };
 
-    // FIXME
-    let tab_width = 4;
-    let use_tabs = false;
+    let formatted = [
+        &source[..range.start],
+        &printed.formatted,
+        &source[range.end..],
+    ]
+    .concat();
+    Output { formatted, cursor }
+}
+
+fn print_doc(doc: &Doc, options: &Options, start_pos: i32, new_line: &str) -> Output {
+    // Groups containing hard lines must break. Breaks are propagated on a copy to keep the doc.
+    let mut doc = doc.clone();
+    propagate_breaks(&mut doc);
+    let output = print_propagated_doc(&doc, options, start_pos, new_line);
+    drop_doc(doc);
+    output
+}
+
+fn print_propagated_doc(doc: &Doc, options: &Options, start_pos: i32, new_line: &str) -> Output {
+    let width = options.print_width as i32;
+    let text_width = options.text_width;
+    let tab_width = options.tab_width.max(1);
+    let use_tabs = options.use_tabs;
 
-    let mut pos: i32 = 0;
+    let mut pos: i32 = start_pos;
 
     // Stack for recursion.
-    let mut commands = vec![
-        Command {
-            indent: root_indent(),
-            mode: Mode::Break,
-            doc,
-        },
-    ];
+    let mut commands = vec![Command {
+        indent: root_indent(),
+        mode: Mode::Break,
+        doc,
+    }];
 
     // what?
     let mut out: Vec<Cow<str>> = Vec::new();
     let mut should_remeasure = false;
     let mut line_suffix: Vec<Command> = Vec::new();
 
-    let err = std::io::stderr();
-    let mut lock = err.lock();
+    // Number of parts printed before the first cursor placeholder.
+    let mut cursor_index = None;
+
+    loop {
+        // Line suffixes pending at the end are printed last.
+        if commands.is_empty() {
+            commands.extend(line_suffix.drain(..).rev());
+        }
 
-    while let Some(Command { indent, mode, doc }) = commands.pop() {
-        lock.write_fmt(format_args!(
-            "{:?} (sr={:?})\n",
-            &Command { indent, mode, doc },
-            should_remeasure
-        )).unwrap();
+        let Command { indent, mode, doc } = match commands.pop() {
+            Some(command) => command,
+            None => break,
+        };
 
         match (doc, mode) {
             (Doc::Source(ref source), _) => {
-                pos += source.len() as i32;
+                pos += text_width.measure(source) as i32;
                 out.push(Cow::Borrowed(source));
             }
-            (Doc::Computed(ComputedDoc::Cursor), _) => unimplemented!(),
+            (Doc::Computed(ComputedDoc::Cursor), _) if cursor_index.is_none() => {
+                cursor_index = Some(out.len());
+            }
             (Doc::Computed(ComputedDoc::Concat { parts }), _) => {
                 for part in parts.iter().rev() {
                     commands.push(Command {
@@ -235,6 +425,11 @@ pub fn print_doc_to_string(doc: &Doc, options: &Options) -> Output {
                     })
                 }
             }
+            (Doc::Computed(ComputedDoc::Label { contents, .. }), _) => commands.push(Command {
+                indent,
+                mode,
+                doc: contents,
+            }),
             (Doc::Computed(ComputedDoc::Indent { contents }), _) => commands.push(Command {
                 indent: make_indent(indent),
                 mode,
@@ -247,15 +442,21 @@ pub fn print_doc_to_string(doc: &Doc, options: &Options) -> Output {
             }),
             (
                 Doc::Computed(ComputedDoc::Group {
-                    breaks, contents, ..
+                    breaks,
+                    contents,
+                    expanded_states,
                 }),
                 Mode::Flat,
-            ) if !should_remeasure =>
-            {
+            ) if !should_remeasure => {
+                let breaks = *breaks;
+                let doc = match expanded_states {
+                    Some(states) if breaks => most_expanded(states, contents),
+                    _ => contents,
+                };
                 commands.push(Command {
                     indent,
-                    mode: if *breaks { Mode::Break } else { Mode::Flat },
-                    doc: contents,
+                    mode: break_if(Mode::Flat, breaks),
+                    doc,
                 })
             }
             (
@@ -268,20 +469,47 @@ pub fn print_doc_to_string(doc: &Doc, options: &Options) -> Output {
             ) => {
                 should_remeasure = false;
 
+                let breaks = *breaks;
                 let next = Command {
                     indent,
                     mode: Mode::Flat,
                     doc: contents,
                 };
                 let reminder = width - pos;
-                let fit = fits(next.clone(), &commands, reminder, false);
+                let fit = fits(next.clone(), &commands, reminder, false, text_width);
 
-                println!("breaks={} fits={}", !*breaks, fit);
-                if !*breaks && fit {
+                if !breaks && fit {
                     commands.push(next);
                 } else {
                     match expanded_states {
-                        Some(_) => unimplemented!(),
+                        Some(states) => {
+                            // Try the rest of states in flat mode before the most expanded one.
+                            let flat_state = if breaks {
+                                None
+                            } else {
+                                states.iter().skip(1).find(|state| {
+                                    let command = Command {
+                                        indent,
+                                        mode: Mode::Flat,
+                                        doc: state,
+                                    };
+                                    fits(command, &commands, reminder, false, text_width)
+                                })
+                            };
+
+                            commands.push(match flat_state {
+                                Some(state) => Command {
+                                    indent,
+                                    mode: Mode::Flat,
+                                    doc: state,
+                                },
+                                None => Command {
+                                    indent,
+                                    mode: Mode::Break,
+                                    doc: most_expanded(states, contents),
+                                },
+                            });
+                        }
                         None => commands.push(Command {
                             indent,
                             mode: Mode::Break,
@@ -304,12 +532,7 @@ pub fn print_doc_to_string(doc: &Doc, options: &Options) -> Output {
                     mode: Mode::Break,
                     doc: content,
                 };
-                let content_fits = fits(
-                    content_flat_command.clone(),
-                    &mut Vec::new(),
-                    width - rem,
-                    true,
-                );
+                let content_fits = fits(content_flat_command.clone(), &[], rem, true, text_width);
 
                 let whitespace = &fill.whitespace;
                 let whitespacce_flat_command = Command {
@@ -346,7 +569,7 @@ pub fn print_doc_to_string(doc: &Doc, options: &Options) -> Output {
                     }
                 };
 
-                let two_contents_fit = fits(two_contents_flat_command, &mut Vec::new(), rem, true);
+                let two_contents_fit = fits(two_contents_flat_command, &[], rem, true, text_width);
 
                 if two_contents_fit {
                     commands.push(remaining_command);
@@ -390,14 +613,11 @@ pub fn print_doc_to_string(doc: &Doc, options: &Options) -> Output {
                 doc: contents,
             }),
             (Doc::Computed(ComputedDoc::LineSuffixBoundary), _) if !line_suffix.is_empty() => {
-                /*
                 commands.push(Command {
                     indent,
                     mode,
-                    doc: Doc::Computed(ComputedDoc::Line(LineKind::Hard)),
+                    doc: &HARD_LINE_DOC,
                 })
-                */
-                unimplemented!()
             }
             (Doc::Computed(ComputedDoc::Line(kind)), Mode::Flat)
                 if !kind.is_hard() && !kind.is_soft() =>
@@ -414,29 +634,28 @@ pub fn print_doc_to_string(doc: &Doc, options: &Options) -> Output {
                 }
 
                 if !line_suffix.is_empty() {
-                    let mut suffix = Vec::new();
-                    std::mem::swap(&mut line_suffix, &mut suffix);
-
-                    for command in suffix.into_iter().rev() {
-                        commands.push(command);
-                    }
+                    // Print the suffixes and then the line again.
+                    commands.push(Command { indent, mode, doc });
+                    commands.extend(line_suffix.drain(..).rev());
                 } else if kind.is_literal() {
-                    out.push(Cow::Borrowed(&new_line));
+                    out.push(Cow::Borrowed(new_line));
                     pos = 0;
                 } else {
-                    // FIXME: trim whitespaces
+                    trim(&mut out);
 
-                    let indent_chars = if use_tabs {
-                        std::iter::repeat("\t").take(indent.indent + indent.tabs)
+                    let (tabs, spaces) = if use_tabs {
+                        (indent.indent + indent.tabs, indent.trailing_spaces)
                     } else {
-                        let len = indent.indent * tab_width + indent.spaces;
-                        std::iter::repeat(" ").take(len)
+                        (0, indent.indent * tab_width + indent.spaces)
                     };
+                    let indent_chars =
+                        std::iter::repeat_n("\t", tabs).chain(std::iter::repeat_n(" ", spaces));
+                    let indent_width = tabs * tab_width + spaces;
 
-                    let break_and_indent = std::iter::once(&new_line as &str)
+                    let break_and_indent = std::iter::once(new_line)
                         .chain(indent_chars)
                         .collect::<String>();
-                    pos = break_and_indent.len() as i32;
+                    pos = indent_width as i32;
                     out.push(Cow::Owned(break_and_indent));
                 }
             }
@@ -444,21 +663,19 @@ pub fn print_doc_to_string(doc: &Doc, options: &Options) -> Output {
         }
     }
 
-    // FIXME
-    // find cursor
-
+    let cursor = cursor_index.map(|index| out[..index].iter().map(|part| part.len()).sum());
     let formatted = out.into_iter().collect::<String>();
-    Output {
-        formatted,
-        cursor: None,
-    }
+    Output { formatted, cursor }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use builders::{concat, fill, group, AlignSize, ComputedDoc, Doc, LineKind, BREAK_PARENT,
-                   CURSOR, LINE};
+    use builders::{
+        align, concat, conditional_group, fill, group, group_ex, indent, line_suffix, AlignSize,
+        ComputedDoc, Doc, GroupSetting, LineKind, BREAK_PARENT, CURSOR, HARD_LINE, LINE,
+        LINE_SUFFIX_BOUNDARY, SOFT_LINE,
+    };
     use std;
     use std::borrow::Cow;
 
@@ -466,7 +683,7 @@ mod tests {
     fn print(print_width: usize, doc: &Doc) -> String {
         let options = Options {
             print_width,
-            new_line: None,
+            ..Options::default()
         };
         let output = print_doc_to_string(doc, &options);
         output.formatted
@@ -511,13 +728,68 @@ mod tests {
             s("mnopq"), // 17..21
         ]));
 
-        // If fit.
-        // assert_eq!(print(80, &doc), "abcdef ghi jk l mnopq");
-
-        // If not fit.
+        // Breaks propagate to the enclosing groups even if they fit.
+        assert_eq!(print(80, &doc), "abcdef\nghi\njk\nl\nmnopq");
         assert_eq!(print(13, &doc), "abcdef\nghi\njk\nl\nmnopq");
     }
 
+    #[test]
+    fn test_hard_line_breaks_group() {
+        let hard_line = Doc::Computed(ComputedDoc::Line(LineKind::Hard));
+        let doc = group(concat(vec![
+            s("a"),
+            LINE,
+            group(concat(vec![s("b"), LINE, s("c")])),
+            hard_line,
+            s("d"),
+        ]));
+        assert_eq!(print(80, &doc), "a\nb c\nd");
+    }
+
+    #[test]
+    fn test_conditional_group() {
+        let broken = |doc: Doc| {
+            group_ex(
+                doc,
+                GroupSetting {
+                    should_break: Some(true),
+                    ..GroupSetting::default()
+                },
+            )
+        };
+        let states = vec![
+            concat(vec![s("f("), s("alpha"), s(", "), s("beta"), s(")")]),
+            concat(vec![
+                s("f(alpha,"),
+                broken(indent(concat(vec![LINE, s("beta)")]))),
+            ]),
+            concat(vec![
+                s("f("),
+                indent(concat(vec![SOFT_LINE, s("alpha,"), LINE, s("beta")])),
+                SOFT_LINE,
+                s(")"),
+            ]),
+        ];
+        let doc = conditional_group(states.clone(), None);
+
+        // The first state if fit.
+        assert_eq!(print(20, &doc), "f(alpha, beta)");
+
+        // The next state whose first line fits.
+        assert_eq!(print(10, &doc), "f(alpha,\n    beta)");
+
+        // The most expanded state if nothing fits.
+        assert_eq!(print(5, &doc), "f(\n    alpha,\n    beta\n)");
+
+        // The most expanded state if the group should break.
+        let setting = GroupSetting {
+            should_break: Some(true),
+            ..GroupSetting::default()
+        };
+        let doc = conditional_group(states, Some(setting));
+        assert_eq!(print(80, &doc), "f(\n    alpha,\n    beta\n)");
+    }
+
     #[test]
     pub fn test_fill() {
         let words = "Lorem ipsum dolor sit amet".split(" ");
@@ -529,5 +801,243 @@ mod tests {
         );
 
         assert_eq!(print(20, &doc), "Lorem ipsum dolor\nsit amet");
+
+        // Contents containing hard lines don't fit in flat mode.
+        let doc = fill(vec![
+            group(concat(vec![s("a"), HARD_LINE, s("b")])),
+            LINE,
+            s("c"),
+        ]);
+        assert_eq!(print(80, &doc), "a\nb\nc");
+
+        // Contents which fit the rest of the line are flat even if the next doesn't fit.
+        let long = "c".repeat(100);
+        let doc = fill(vec![concat(vec![s("a"), LINE, s("b")]), LINE, s(&long)]);
+        assert_eq!(print(80, &doc), format!("a b\n{}", long));
+        let doc = concat(vec![s("x".repeat(79)), doc]);
+        assert_eq!(print(80, &doc), format!("{}a\nb\n{}", "x".repeat(79), long));
+    }
+
+    #[test]
+    fn test_line_suffix() {
+        let doc = concat(vec![
+            s("a"),
+            line_suffix(s(" // x")),
+            s(";"),
+            HARD_LINE,
+            s("b"),
+            line_suffix(s(" // y")),
+            LINE_SUFFIX_BOUNDARY,
+            s("c"),
+            LINE_SUFFIX_BOUNDARY,
+            s("d"),
+            line_suffix(s(" // z")),
+        ]);
+        assert_eq!(print(80, &doc), "a; // x\nb // y\ncd // z");
+    }
+
+    #[test]
+    fn test_text_width() {
+        let doc = group(concat(vec![s("日本語"), LINE, s("テキスト")]));
+        assert_eq!(print(15, &doc), "日本語 テキスト");
+        assert_eq!(print(14, &doc), "日本語\nテキスト");
+
+        let options = Options {
+            print_width: 15,
+            text_width: TextWidth::Bytes,
+            ..Options::default()
+        };
+        let output = print_doc_to_string(&doc, &options);
+        assert_eq!(output.formatted, "日本語\nテキスト");
+    }
+
+    #[test]
+    fn test_trim() {
+        let hard_line = Doc::Computed(ComputedDoc::Line(LineKind::Hard));
+        let doc = concat(vec![
+            s("a"),
+            s(" \t"),
+            s(""),
+            hard_line.clone(),
+            indent(concat(vec![s("b "), hard_line.clone(), hard_line, s("c")])),
+        ]);
+
+        // Indentation of empty lines is also trimmed.
+        assert_eq!(print(80, &doc), "a\nb\n\n    c");
+    }
+
+    #[test]
+    fn test_cursor() {
+        let options = Options {
+            print_width: 8,
+            ..Options::default()
+        };
+        let doc = group(concat(vec![
+            s("alpha"),
+            LINE,
+            s("be"),
+            CURSOR,
+            s("ta"),
+            CURSOR,
+        ]));
+        let output = print_doc_to_string(&doc, &options);
+        assert_eq!(output.formatted, "alpha\nbeta");
+        assert_eq!(output.cursor, Some(8));
+
+        let output = print_doc_to_string(&s("alpha"), &options);
+        assert_eq!(output.cursor, None);
+    }
+
+    #[test]
+    fn test_print_doc_in_range() {
+        let options = Options {
+            print_width: 18,
+            ..Options::default()
+        };
+        let source = "fn f() {\n    g(a,b);\n}\n";
+        let range = 13..20;
+        assert_eq!(&source[range.clone()], "g(a,b);");
+
+        let doc = group(concat(vec![
+            s("g("),
+            indent(concat(vec![SOFT_LINE, s("alpha,"), LINE, s("beta")])),
+            SOFT_LINE,
+            s(");"),
+        ]));
+        let output = print_doc_in_range(&doc, &options, source, range.clone(), Some(20));
+        assert_eq!(
+            output.formatted,
+            "fn f() {\n    g(\n        alpha,\n        beta\n    );\n}\n"
+        );
+        assert_eq!(&output.formatted[output.cursor.unwrap()..], "\n}\n");
+
+        let doc = s("g(a, b);");
+        let output = print_doc_in_range(&doc, &options, source, range.clone(), Some(3));
+        assert_eq!(output.formatted, "fn f() {\n    g(a, b);\n}\n");
+        assert_eq!(output.cursor, Some(3));
+
+        // The placeholder takes precedence over the cursor in the source.
+        let doc = concat(vec![s("g(a, "), CURSOR, s("b);")]);
+        let output = print_doc_in_range(&doc, &options, source, range, Some(3));
+        assert_eq!(&output.formatted[output.cursor.unwrap()..], "b);\n}\n");
+    }
+
+    #[test]
+    fn test_use_tabs() {
+        let doc = concat(vec![
+            s("{"),
+            indent(concat(vec![
+                SOFT_LINE,
+                s("a"),
+                align(AlignSize::Number(2), concat(vec![SOFT_LINE, s("b")])),
+            ])),
+            SOFT_LINE,
+            s("}"),
+        ]);
+
+        let options = Options {
+            use_tabs: true,
+            ..Options::default()
+        };
+        let output = print_doc_to_string(&doc, &options);
+        assert_eq!(output.formatted, "{\n\ta\n\t  b\n}");
+
+        // An align followed by an indent takes a tab.
+        let nested = align(
+            AlignSize::Number(2),
+            concat(vec![
+                s("a"),
+                indent(concat(vec![SOFT_LINE, s("b")])),
+                SOFT_LINE,
+                s("c"),
+            ]),
+        );
+        let nested = concat(vec![
+            s("{"),
+            indent(concat(vec![SOFT_LINE, nested])),
+            SOFT_LINE,
+            s("}"),
+        ]);
+        let output = print_doc_to_string(&nested, &options);
+        assert_eq!(output.formatted, "{\n\ta\n\t\t\tb\n\t  c\n}");
+
+        let options = Options {
+            tab_width: 2,
+            ..Options::default()
+        };
+        let output = print_doc_to_string(&doc, &options);
+        assert_eq!(output.formatted, "{\n  a\n    b\n}");
+    }
+
+    #[test]
+    fn test_end_of_line() {
+        assert_eq!(EndOfLine::detect("a\r\nb\n"), EndOfLine::Crlf);
+        assert_eq!(EndOfLine::detect("a\rb\n"), EndOfLine::Cr);
+        assert_eq!(EndOfLine::detect("a\nb\r\n"), EndOfLine::Lf);
+        assert_eq!(EndOfLine::detect("a"), EndOfLine::Lf);
+
+        let hard_line = Doc::Computed(ComputedDoc::Line(LineKind::Hard));
+        let doc = concat(vec![s("a"), indent(concat(vec![hard_line, s("b")]))]);
+        let options = Options {
+            end_of_line: EndOfLine::Crlf,
+            ..Options::default()
+        };
+        let output = print_doc_to_string(&doc, &options);
+        assert_eq!(output.formatted, "a\r\n    b");
+
+        // Auto is detected from the source in range formatting.
+        let options = Options {
+            end_of_line: EndOfLine::Auto,
+            ..Options::default()
+        };
+        let output = print_doc_to_string(&doc, &options);
+        assert_eq!(output.formatted, "a\n    b");
+        let output = print_doc_in_range(&doc, &options, "x\r\ny\r\n", 3..4, None);
+        assert_eq!(output.formatted, "x\r\na\r\n    b\r\n");
+    }
+
+    #[test]
+    fn test_print_doc_in_range_with_tabs() {
+        let options = Options {
+            use_tabs: true,
+            ..Options::default()
+        };
+        let source = "{\n\t{\n\t\tx;\n\t}\n}";
+        let range = 7..9;
+        assert_eq!(&source[range.clone()], "x;");
+
+        let hard_line = Doc::Computed(ComputedDoc::Line(LineKind::Hard));
+        let doc = concat(vec![s("x;"), hard_line, s("y;")]);
+        let output = print_doc_in_range(&doc, &options, source, range.clone(), None);
+        assert_eq!(output.formatted, "{\n\t{\n\t\tx;\n\t\ty;\n\t}\n}");
+
+        // Tab stops are counted with spaces too.
+        let options = Options {
+            tab_width: 2,
+            ..Options::default()
+        };
+        let output = print_doc_in_range(&doc, &options, source, range.clone(), None);
+        assert_eq!(output.formatted, "{\n\t{\n\t\tx;\n    y;\n\t}\n}");
+
+        // Zero tab width is treated as 1.
+        let options = Options {
+            tab_width: 0,
+            ..Options::default()
+        };
+        let output = print_doc_in_range(&doc, &options, source, range, None);
+        assert_eq!(output.formatted, "{\n\t{\n\t\tx;\n  y;\n\t}\n}");
+    }
+
+    #[test]
+    fn test_deep_doc() {
+        let mut doc = concat(vec![s("a"), LINE, s("b")]);
+        for _ in 0..100_000 {
+            doc = group(concat(vec![doc]));
+        }
+
+        assert_eq!(print(80, &doc), "a b");
+        let output = print_doc_in_range(&doc, &Options::default(), "{ x }", 2..3, None);
+        assert_eq!(output.formatted, "{ a b }");
+        drop_doc(doc);
     }
 }
@@ -1,4 +1,6 @@
-use builders::{ComputedDoc, Doc};
+use builders::{ComputedDoc, Doc, Fill};
+use std::collections::HashSet;
+use std::mem;
 
 pub fn is_empty(doc: &Doc) -> bool {
     match doc {
@@ -10,18 +12,24 @@ pub fn is_empty(doc: &Doc) -> bool {
 pub fn will_break(doc: &Doc) -> bool {
     find_in_doc(
         doc,
-        |doc| {
-            match doc {
-                Doc::Computed(ComputedDoc::Group { breaks, .. }) if *breaks => Some(true),
-                // Doc::Computed(ComputedDoc::Line(LineKind::Hard)) => true,
-                Doc::Computed(ComputedDoc::BreakParent) => Some(true),
-                _ => None,
-            }
+        |doc| match doc {
+            Doc::Computed(ComputedDoc::Group { breaks, .. }) if *breaks => Some(true),
+            Doc::Computed(ComputedDoc::Line(kind)) if kind.is_hard() => Some(true),
+            Doc::Computed(ComputedDoc::BreakParent) => Some(true),
+            _ => None,
         },
         false,
     )
 }
 
+/// Gets the label of the doc, if it's created by `label`.
+pub fn get_label(doc: &Doc) -> Option<&str> {
+    match doc {
+        Doc::Computed(ComputedDoc::Label { label, .. }) => Some(label),
+        _ => None,
+    }
+}
+
 pub fn is_line_next(doc: &Doc) -> bool {
     find_in_doc(
         doc,
@@ -34,30 +42,559 @@ pub fn is_line_next(doc: &Doc) -> bool {
     )
 }
 
-fn find_in_doc<T, F>(_doc: &Doc, _f: F, _default_value: T) -> T
+/// Which children of a group with expanded states are visited.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum GroupChildren {
+    Contents,
+    ExpandedStates,
+    Both,
+}
+
+fn children(doc: &Doc, groups: GroupChildren) -> Vec<&Doc> {
+    let doc = match doc {
+        Doc::Source(_) => return vec![],
+        Doc::Computed(doc) => doc,
+    };
+
+    match doc {
+        ComputedDoc::Concat { parts } => parts.iter().collect(),
+        ComputedDoc::Fill(fill) => vec![&fill.first, &fill.whitespace, &fill.second],
+        ComputedDoc::IfBreak {
+            break_contents,
+            flat_contents,
+        } => break_contents
+            .iter()
+            .chain(flat_contents.iter())
+            .map(|contents| &**contents)
+            .collect(),
+        ComputedDoc::Group {
+            contents,
+            expanded_states,
+            ..
+        } => match (groups, expanded_states) {
+            (GroupChildren::Contents, _) | (_, None) => vec![contents],
+            (GroupChildren::ExpandedStates, Some(states)) => states.iter().collect(),
+            (GroupChildren::Both, Some(states)) => {
+                std::iter::once(&**contents).chain(states.iter()).collect()
+            }
+        },
+        ComputedDoc::Indent { contents }
+        | ComputedDoc::Align { contents, .. }
+        | ComputedDoc::ReseltAlign { contents, .. }
+        | ComputedDoc::LineSuffix { contents }
+        | ComputedDoc::Label { contents, .. } => vec![contents],
+        ComputedDoc::LineSuffixBoundary
+        | ComputedDoc::BreakParent
+        | ComputedDoc::Line(_)
+        | ComputedDoc::Cursor => vec![],
+    }
+}
+
+fn children_mut(doc: &mut Doc) -> Vec<&mut Doc> {
+    let doc = match doc {
+        Doc::Source(_) => return vec![],
+        Doc::Computed(doc) => doc,
+    };
+
+    match doc {
+        ComputedDoc::Concat { parts } => parts.iter_mut().collect(),
+        ComputedDoc::Fill(fill) => {
+            let Fill {
+                first,
+                whitespace,
+                second,
+            } = &mut **fill;
+            vec![first, whitespace, second]
+        }
+        ComputedDoc::IfBreak {
+            break_contents,
+            flat_contents,
+        } => break_contents
+            .iter_mut()
+            .chain(flat_contents.iter_mut())
+            .map(|contents| &mut **contents)
+            .collect(),
+        ComputedDoc::Group {
+            contents,
+            expanded_states,
+            ..
+        } => std::iter::once(&mut **contents)
+            .chain(
+                expanded_states
+                    .iter_mut()
+                    .flat_map(|states| states.iter_mut()),
+            )
+            .collect(),
+        ComputedDoc::Indent { contents }
+        | ComputedDoc::Align { contents, .. }
+        | ComputedDoc::ReseltAlign { contents, .. }
+        | ComputedDoc::LineSuffix { contents }
+        | ComputedDoc::Label { contents, .. } => vec![contents],
+        ComputedDoc::LineSuffixBoundary
+        | ComputedDoc::BreakParent
+        | ComputedDoc::Line(_)
+        | ComputedDoc::Cursor => vec![],
+    }
+}
+
+/// Creates a doc of the same kind with the specified children,
+/// in the order of `children(doc, GroupChildren::Both)`.
+fn with_children(doc: &Doc, children: Vec<Doc>) -> Doc {
+    let doc = match doc {
+        Doc::Source(source) => return Doc::Source(source.clone()),
+        Doc::Computed(doc) => doc,
+    };
+
+    fn next<I: Iterator<Item = Doc>>(children: &mut I) -> Box<Doc> {
+        Box::new(children.next().unwrap())
+    }
+
+    let mut children = children.into_iter();
+    let doc = match doc {
+        ComputedDoc::Concat { .. } => ComputedDoc::Concat {
+            parts: children.collect(),
+        },
+        ComputedDoc::Fill(_) => ComputedDoc::Fill(Box::new(Fill {
+            first: *next(&mut children),
+            whitespace: *next(&mut children),
+            second: *next(&mut children),
+        })),
+        ComputedDoc::IfBreak {
+            break_contents,
+            flat_contents,
+        } => ComputedDoc::IfBreak {
+            break_contents: break_contents.as_ref().map(|_| next(&mut children)),
+            flat_contents: flat_contents.as_ref().map(|_| next(&mut children)),
+        },
+        ComputedDoc::Group {
+            breaks,
+            expanded_states,
+            ..
+        } => ComputedDoc::Group {
+            contents: next(&mut children),
+            breaks: *breaks,
+            expanded_states: expanded_states.as_ref().map(|_| children.collect()),
+        },
+        ComputedDoc::Indent { .. } => ComputedDoc::Indent {
+            contents: next(&mut children),
+        },
+        ComputedDoc::Align { n, .. } => ComputedDoc::Align {
+            contents: next(&mut children),
+            n: *n,
+        },
+        ComputedDoc::ReseltAlign { n, .. } => ComputedDoc::ReseltAlign {
+            contents: next(&mut children),
+            n: *n,
+        },
+        ComputedDoc::LineSuffix { .. } => ComputedDoc::LineSuffix {
+            contents: next(&mut children),
+        },
+        ComputedDoc::Label { label, .. } => ComputedDoc::Label {
+            label: label.clone(),
+            contents: next(&mut children),
+        },
+        doc => doc.clone(),
+    };
+    Doc::Computed(doc)
+}
+
+pub(crate) enum Step<'a> {
+    Enter(&'a Doc),
+    Exit(&'a Doc),
+}
+
+/// Visits docs in depth-first order without recursion.
+/// Children are skipped if `visit` returns false at enter. Exit is visited anyway.
+pub(crate) fn walk<'a, F>(doc: &'a Doc, groups: GroupChildren, mut visit: F)
+where
+    F: FnMut(Step<'a>) -> bool,
+{
+    let mut stack = vec![Step::Enter(doc)];
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Enter(doc) => {
+                stack.push(Step::Exit(doc));
+                if visit(Step::Enter(doc)) {
+                    for child in children(doc, groups).into_iter().rev() {
+                        stack.push(Step::Enter(child));
+                    }
+                }
+            }
+            Step::Exit(doc) => {
+                visit(Step::Exit(doc));
+            }
+        }
+    }
+}
+
+/// Finds the first doc for which `f` returns some value, in depth-first order.
+/// Groups with expanded states are searched only in their contents.
+pub fn find_in_doc<T, F>(doc: &Doc, mut f: F, default_value: T) -> T
 where
-    F: Fn(&Doc) -> Option<T>,
+    F: FnMut(&Doc) -> Option<T>,
 {
-    unimplemented!()
+    let mut result = None;
+    walk(doc, GroupChildren::Contents, |step| match step {
+        Step::Enter(_) if result.is_some() => false,
+        Step::Enter(doc) => {
+            result = f(doc);
+            true
+        }
+        Step::Exit(_) => true,
+    });
+    result.unwrap_or(default_value)
 }
 
+/// Visits docs in depth-first order without recursion.
+/// Children of a doc are skipped if `on_enter` returns false.
+/// If `should_traverse_conditional_groups` is set, expanded states of groups are visited
+/// instead of their contents.
 pub fn traverse_doc<FEnter, FExit>(
-    _doc: &Doc,
-    _on_enter: FEnter,
-    _on_exit: FExit,
-    _should_traverse_conditional_groups: bool,
+    doc: &Doc,
+    mut on_enter: FEnter,
+    mut on_exit: FExit,
+    should_traverse_conditional_groups: bool,
 ) where
-    FEnter: Fn(&Doc),
-    FExit: Fn(&Doc),
+    FEnter: FnMut(&Doc) -> bool,
+    FExit: FnMut(&Doc),
 {
-    unimplemented!()
+    let groups = if should_traverse_conditional_groups {
+        GroupChildren::ExpandedStates
+    } else {
+        GroupChildren::Contents
+    };
+    walk(doc, groups, |step| match step {
+        Step::Enter(doc) => on_enter(doc),
+        Step::Exit(doc) => {
+            on_exit(doc);
+            true
+        }
+    })
 }
 
-pub fn map_doc<F>(_doc: &Doc, _f: &F) -> Doc
+/// Creates a doc by applying `f` to each doc from the bottom up.
+/// `f` receives docs whose children are already mapped.
+pub fn map_doc<F>(doc: &Doc, f: &F) -> Doc
 where
     F: Fn(&Doc) -> Doc,
 {
-    unimplemented!()
+    map_doc_owned(doc, |doc| {
+        let mapped = f(&doc);
+        drop_doc(doc);
+        mapped
+    })
+}
+
+/// Copies the doc without recursion. `Doc::clone` does this.
+pub(crate) fn clone_doc(doc: &Doc) -> Doc {
+    map_doc_owned(doc, |doc| doc)
+}
+
+/// Drops the doc without recursion.
+/// Dropping a deeply nested doc as usual overflows the stack.
+pub fn drop_doc(doc: Doc) {
+    let mut stack = vec![doc];
+    while let Some(mut doc) = stack.pop() {
+        for child in children_mut(&mut doc) {
+            stack.push(mem::replace(child, Doc::Source(String::new())));
+        }
+    }
+}
+
+/// Like `map_doc`, but `f` takes the doc, so that it can move the mapped children.
+pub(crate) fn map_doc_owned<F>(doc: &Doc, f: F) -> Doc
+where
+    F: Fn(Doc) -> Doc,
+{
+    // Mapped docs, which the parent takes at exit.
+    let mut mapped = Vec::new();
+    let mut counts = Vec::new();
+    walk(doc, GroupChildren::Both, |step| {
+        match step {
+            Step::Enter(doc) => counts.push(children(doc, GroupChildren::Both).len()),
+            Step::Exit(doc) => {
+                let count = counts.pop().unwrap();
+                let children = mapped.split_off(mapped.len() - count);
+                mapped.push(f(with_children(doc, children)));
+            }
+        }
+        true
+    });
+    mapped.pop().unwrap()
+}
+
+/// Finds groups which must break but aren't marked, i.e., groups containing hard lines,
+/// `BreakParent` or broken groups. Conditional groups aren't broken by their contents.
+/// Docs are identified by address.
+fn find_breaking_groups(doc: &Doc) -> HashSet<*const Doc> {
+    let mut found = HashSet::new();
+
+    // Whether each doc being visited contains a forced break.
+    let mut stack = vec![false];
+    walk(doc, GroupChildren::Both, |step| {
+        let doc = match step {
+            Step::Enter(_) => {
+                stack.push(false);
+                return true;
+            }
+            Step::Exit(doc) => doc,
+        };
+
+        let contains_break = stack.pop().unwrap();
+        let breaks = match doc {
+            Doc::Computed(ComputedDoc::Line(kind)) => kind.is_hard(),
+            Doc::Computed(ComputedDoc::BreakParent) => true,
+            Doc::Computed(ComputedDoc::Group {
+                breaks,
+                expanded_states: Some(_),
+                ..
+            }) => *breaks,
+            Doc::Computed(ComputedDoc::Group { breaks, .. }) => {
+                if contains_break && !*breaks {
+                    found.insert(doc as *const Doc);
+                }
+                *breaks || contains_break
+            }
+            _ => contains_break,
+        };
+
+        let parent = stack.last_mut().unwrap();
+        *parent = *parent || breaks;
+        true
+    });
+    found
+}
+
+/// Marks groups broken if they contain hard lines, `BreakParent` or broken groups.
+pub fn propagate_breaks(doc: &mut Doc) {
+    let found = find_breaking_groups(doc);
+    if found.is_empty() {
+        return;
+    }
+
+    let mut stack = vec![doc];
+    while let Some(doc) = stack.pop() {
+        if found.contains(&(doc as *const Doc)) {
+            if let Doc::Computed(ComputedDoc::Group { breaks, .. }) = doc {
+                *breaks = true;
+            }
+        }
+        stack.extend(children_mut(doc));
+    }
+}
+
+/// Removes hard lines and newlines at the end of the doc,
+/// e.g. to put a doc of a whole file into another doc.
+pub fn strip_trailing_hard_line(doc: &Doc) -> Doc {
+    match doc {
+        Doc::Source(source) => Doc::Source(source.trim_end_matches(['\n', '\r']).to_owned()),
+        Doc::Computed(ComputedDoc::Concat { parts }) => {
+            // Empty parts at the end are removed too.
+            let mut parts = parts.clone();
+            while let Some(last) = parts.pop() {
+                match strip_trailing_hard_line(&last) {
+                    Doc::Computed(ComputedDoc::Line(kind)) if kind.is_hard() => {}
+                    Doc::Computed(ComputedDoc::Concat { parts: ref inner }) if inner.is_empty() => {
+                    }
+                    ref last if is_empty(last) => {}
+                    last => {
+                        parts.push(last);
+                        break;
+                    }
+                }
+            }
+            Doc::Computed(ComputedDoc::Concat { parts })
+        }
+        Doc::Computed(ComputedDoc::Label { label, contents }) => {
+            Doc::Computed(ComputedDoc::Label {
+                label: label.clone(),
+                contents: Box::new(strip_trailing_hard_line(contents)),
+            })
+        }
+        _ => doc.clone(),
+    }
 }
 
-// FIXME: port
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builders::{
+        concat, conditional_group, fill, group, if_break, indent, label, ComputedDoc, Doc,
+        LineKind, BREAK_PARENT, HARD_LINE, LINE, SOFT_LINE,
+    };
+
+    fn s<T: ToString>(source: T) -> Doc {
+        Doc::Source(source.to_string())
+    }
+
+    fn is_broken(doc: &Doc) -> bool {
+        match doc {
+            Doc::Computed(ComputedDoc::Group { breaks, .. }) => *breaks,
+            _ => panic!("Expected a group."),
+        }
+    }
+
+    fn sources(doc: &Doc) -> Vec<String> {
+        let mut out = Vec::new();
+        traverse_doc(
+            doc,
+            |doc| {
+                if let Doc::Source(source) = doc {
+                    out.push(source.clone());
+                }
+                true
+            },
+            |_| {},
+            false,
+        );
+        out
+    }
+
+    #[test]
+    fn test_traverse_doc() {
+        let doc = concat(vec![
+            s("a"),
+            indent(concat(vec![s("b"), LINE, s("c")])),
+            if_break(Some(s("d")), Some(s("e"))),
+            fill(vec![s("f"), LINE, s("g")]),
+            conditional_group(vec![s("h"), s("i")], None),
+        ]);
+        assert_eq!(sources(&doc), vec!["a", "b", "c", "d", "e", "f", "g", "h"]);
+
+        let mut entered = Vec::new();
+        let mut exited = 0;
+        traverse_doc(
+            &doc,
+            |doc| {
+                if let Doc::Source(source) = doc {
+                    entered.push(source.clone());
+                }
+                // Skip children of indent.
+                !matches!(doc, Doc::Computed(ComputedDoc::Indent { .. }))
+            },
+            |_| exited += 1,
+            true,
+        );
+        assert_eq!(entered, vec!["a", "d", "e", "f", "g", "h", "i"]);
+        assert_eq!(exited, 13);
+    }
+
+    #[test]
+    fn test_find_in_doc() {
+        let doc = concat(vec![s("a"), group(concat(vec![SOFT_LINE, s("b")]))]);
+        assert!(!is_line_next(&doc));
+        assert!(is_line_next(&group(concat(vec![LINE, s("a")]))));
+        assert!(!will_break(&doc));
+        assert!(will_break(&concat(vec![
+            s("a"),
+            indent(concat(vec![BREAK_PARENT]))
+        ])));
+        assert!(will_break(&Doc::Computed(ComputedDoc::Line(
+            LineKind::Hard
+        ))));
+    }
+
+    #[test]
+    fn test_map_doc() {
+        let doc = concat(vec![
+            s("a"),
+            group(concat(vec![s("b"), LINE, s("c")])),
+            conditional_group(vec![s("d"), s("e")], None),
+        ]);
+        let mapped = map_doc(&doc, &|doc| match doc {
+            Doc::Source(source) => s(source.to_uppercase()),
+            Doc::Computed(ComputedDoc::Line(_)) => SOFT_LINE,
+            doc => doc.clone(),
+        });
+        assert_eq!(sources(&mapped), vec!["A", "B", "C", "D"]);
+        assert!(!is_line_next(&concat(vec![s("x"), mapped.clone()])));
+
+        let mut states = Vec::new();
+        traverse_doc(
+            &mapped,
+            |doc| {
+                if let Doc::Source(source) = doc {
+                    states.push(source.clone());
+                }
+                true
+            },
+            |_| {},
+            true,
+        );
+        assert_eq!(states, vec!["A", "B", "C", "D", "E"]);
+    }
+
+    #[test]
+    fn test_propagate_breaks() {
+        let mut doc = group(concat(vec![
+            s("a"),
+            group(concat(vec![s("b"), group(concat(vec![BREAK_PARENT]))])),
+            group(s("c")),
+            conditional_group(vec![group(concat(vec![BREAK_PARENT]))], None),
+        ]));
+        propagate_breaks(&mut doc);
+
+        assert!(is_broken(&doc));
+        let parts = match &doc {
+            Doc::Computed(ComputedDoc::Group { contents, .. }) => match &**contents {
+                Doc::Computed(ComputedDoc::Concat { parts }) => parts.clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        assert!(is_broken(&parts[1]));
+        assert!(!is_broken(&parts[2]));
+
+        // Conditional groups aren't broken by contents, but the states are.
+        assert!(!is_broken(&parts[3]));
+        match &parts[3] {
+            Doc::Computed(ComputedDoc::Group {
+                contents,
+                expanded_states: Some(states),
+                ..
+            }) => {
+                assert!(is_broken(contents));
+                assert!(is_broken(&states[0]));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_strip_trailing_hard_line() {
+        let doc = label(
+            "x",
+            concat(vec![
+                s("a"),
+                HARD_LINE,
+                concat(vec![s("b\n"), HARD_LINE, concat(vec![])]),
+                HARD_LINE,
+                s(""),
+            ]),
+        );
+        let doc = strip_trailing_hard_line(&doc);
+        assert_eq!(get_label(&doc), Some("x"));
+        assert_eq!(sources(&doc), vec!["a", "b"]);
+        assert!(will_break(&doc));
+    }
+
+    #[test]
+    fn test_deep_doc() {
+        let mut doc = concat(vec![s("x"), BREAK_PARENT]);
+        for _ in 0..100_000 {
+            doc = group(concat(vec![doc]));
+        }
+
+        assert!(will_break(&doc));
+        assert_eq!(sources(&doc), vec!["x"]);
+        assert_eq!(find_breaking_groups(&doc).len(), 100_000);
+        propagate_breaks(&mut doc);
+        assert!(is_broken(&doc));
+
+        let copy = doc.clone();
+        assert!(is_broken(&copy));
+        assert_eq!(sources(&copy), vec!["x"]);
+        drop_doc(copy);
+        drop_doc(doc);
+    }
+}
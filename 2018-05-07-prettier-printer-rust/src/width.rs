@@ -0,0 +1,61 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// How to measure the width of text to fit lines into the print width.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TextWidth {
+    /// Number of bytes in UTF-8, which is right for ASCII text only.
+    Bytes,
+
+    /// Number of columns in terminals, based on East Asian Width.
+    /// Each grapheme cluster (e.g. an emoji sequence or a character with combining marks)
+    /// takes at most 2 columns.
+    #[default]
+    Unicode,
+
+    /// Same as `Unicode` except that characters of ambiguous width are wide,
+    /// as in CJK contexts.
+    UnicodeCjk,
+}
+
+impl TextWidth {
+    pub fn measure(self, text: &str) -> usize {
+        match self {
+            TextWidth::Bytes => text.len(),
+            TextWidth::Unicode => text
+                .graphemes(true)
+                .map(|grapheme| grapheme.width().min(2))
+                .sum(),
+            TextWidth::UnicodeCjk => text
+                .graphemes(true)
+                .map(|grapheme| grapheme.width_cjk().min(2))
+                .sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure() {
+        let cases = vec![
+            ("hello", 5, 5, 5),
+            ("日本語", 9, 6, 6),
+            ("ｱｲｳ", 9, 3, 3),
+            // e + combining acute accent
+            ("e\u{301}", 3, 1, 1),
+            // family emoji joined with ZWJ
+            ("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}", 18, 2, 2),
+            // ambiguous width
+            ("①②", 6, 2, 4),
+        ];
+
+        for (text, bytes, unicode, cjk) in cases {
+            assert_eq!(TextWidth::Bytes.measure(text), bytes, "{}", text);
+            assert_eq!(TextWidth::Unicode.measure(text), unicode, "{}", text);
+            assert_eq!(TextWidth::UnicodeCjk.measure(text), cjk, "{}", text);
+        }
+    }
+}